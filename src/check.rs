@@ -0,0 +1,241 @@
+use crate::error::LogoError;
+use crate::interpretator::primitives::is_primitive;
+use crate::interpretator::primitives::outputs;
+use crate::interpretator::primitives::signatures;
+use crate::interpretator::value::Value;
+use crate::parser::fold_case;
+use crate::parser::parse_statement;
+use crate::parser::scan_definitions;
+use crate::parser::Exp;
+use crate::parser::Procedure;
+use crate::parser::Stat;
+use crate::tokenizer::Kind;
+use crate::tokenizer::Pos;
+use crate::tokenizer::Token;
+use crate::unsee::Unsee;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::ops::BitOrAssign;
+
+/// A problem found in the program without running it.
+pub struct Diagnostic {
+    pub pos: Pos,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}: {}", self.pos, self.message)
+    }
+}
+
+/// How running a piece of code may end.
+#[derive(Clone, Copy, Default)]
+struct Flow {
+    /// Some path calls `output`.
+    outputs: bool,
+    /// Some path calls `stop`.
+    stops: bool,
+    /// Every path ends with `stop` or `output`.
+    exits: bool,
+}
+
+/// Adds what may happen in code which runs only sometimes.
+impl BitOrAssign for Flow {
+    fn bitor_assign(&mut self, rhs: Flow) {
+        self.outputs |= rhs.outputs;
+        self.stops |= rhs.stops;
+    }
+}
+
+struct Checker {
+    signs: HashMap<String, usize>,
+    /// User procedures with an `output` in their body.
+    outputs: HashSet<String>,
+    /// Variables given a value somewhere: inputs and `make` with a word.
+    bound: HashSet<String>,
+    /// A `make` of a computed name, which could bind any variable.
+    binds_any: bool,
+    vars: Vec<(String, Pos)>,
+    diags: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn report(&mut self, pos: Pos, message: impl Display) {
+        self.diags.push(Diagnostic {
+            pos,
+            message: message.to_string(),
+        });
+    }
+
+    /// Collects the signatures of all `to` definitions, wherever they are,
+    /// as a procedure can be called before its definition is reached.
+    fn definitions(&mut self, tokens: &[Token]) {
+        for def in scan_definitions(tokens) {
+            let (name, n) = (def.name, def.argv.len());
+            let key = fold_case(&name.text);
+            self.bound
+                .extend(def.argv.iter().map(|t| fold_case(&t.text[1..])));
+            let output = |t: &Token| t.kind == Kind::Name && t.text.eq_ignore_ascii_case("output");
+            if def.body.iter().any(output) {
+                self.outputs.insert(key.clone());
+            }
+            if is_primitive(&key) {
+                self.report(name.pos(), format!("{} is a primitive", name.text));
+            } else if self.signs.get(&key).is_some_and(|m| *m != n) {
+                let msg = format!("{} is redefined with {n} inputs", name.text);
+                self.report(name.pos(), msg);
+            } else {
+                self.signs.insert(key, n);
+            }
+        }
+    }
+
+    /// Checks a sequence of statements: a program, a procedure body or a
+    /// list that is run.
+    fn block(&mut self, tokens: &[Token]) -> Flow {
+        let mut flow = Flow::default();
+        let mut dead = false;
+        let mut exit = String::new();
+        let mut at = 0;
+        while at < tokens.len() {
+            let start = tokens[at].pos();
+            let mut iter = Unsee::wrap(tokens[at..].iter());
+            let stat = match parse_statement(&self.signs, &mut iter) {
+                Ok(Some(stat)) => stat,
+                Ok(None) => break,
+                Err(e) => {
+                    let pos = e.pos().unwrap_or(start);
+                    at = match e.cause() {
+                        LogoError::MissingEnd(_) => tokens.len(),
+                        _ => resync(tokens, at, pos),
+                    };
+                    self.report(pos, e.cause());
+                    continue;
+                }
+            };
+            at += iter.consumed();
+            if flow.exits {
+                // One report for all the code that follows.
+                if !dead {
+                    self.report(start, format!("code after {exit} is never run"));
+                    dead = true;
+                }
+                continue;
+            }
+            match stat {
+                Stat::ProcDef(proc) => self.procedure(&proc, start),
+                Stat::Exp(e) => {
+                    if self.has_value(&e) {
+                        let msg = match &e {
+                            Exp::Const(v) => LogoError::UnusedValue(v.clone()).to_string(),
+                            e => format!("You don't say what to do with {e}"),
+                        };
+                        self.report(start, msg);
+                    }
+                    flow |= self.exp(&e);
+                    if let Exp::Call(pr, _, _) = &e {
+                        flow.exits = matches!(&fold_case(pr)[..], "stop" | "output");
+                        exit = pr.clone();
+                    }
+                }
+            }
+        }
+        flow
+    }
+
+    fn procedure(&mut self, proc: &Procedure, pos: Pos) {
+        let flow = self.block(proc.get_body().tokens());
+        if flow.outputs && (flow.stops || !flow.exits) {
+            let msg = format!(
+                "{} sometimes outputs a value and sometimes doesn't",
+                proc.get_name()
+            );
+            self.report(pos, msg);
+        }
+    }
+
+    /// Whether the statement `e` leaves a value nobody uses.
+    fn has_value(&self, e: &Exp) -> bool {
+        match e {
+            Exp::Call(pr, _, _) => {
+                let key = fold_case(pr);
+                outputs(&key) || !is_primitive(&key) && self.outputs.contains(&key)
+            }
+            _ => true,
+        }
+    }
+
+    fn exp(&mut self, e: &Exp) -> Flow {
+        let mut flow = Flow::default();
+        match e {
+            Exp::Call(pr, args, _) => {
+                for arg in args {
+                    flow |= self.exp(arg);
+                }
+                match (&fold_case(pr)[..], &args[..]) {
+                    ("output", _) => flow.outputs = true,
+                    ("stop", _) => flow.stops = true,
+                    ("make", [Exp::Const(Value::Str(name)), _]) => {
+                        self.bound.insert(fold_case(name));
+                    }
+                    ("make", _) => self.binds_any = true,
+                    ("repeat" | "if" | "filled", [_, Exp::Const(Value::List(code))]) => {
+                        flow |= self.block(code.tokens());
+                    }
+                    _ => (),
+                }
+            }
+            Exp::Oper(_, x, y, _) => {
+                flow |= self.exp(x);
+                flow |= self.exp(y);
+            }
+            Exp::Var(name, span) => self.vars.push((name.clone(), span.start)),
+            Exp::Const(_) => (),
+        }
+        flow
+    }
+}
+
+/// Where to continue after a statement starting at `at` failed to parse
+/// at `pos`: the first token on a later line, outside of brackets.
+fn resync(tokens: &[Token], at: usize, pos: Pos) -> usize {
+    let mut depth = 0;
+    for (i, tok) in tokens.iter().enumerate().skip(at) {
+        if i > at && depth == 0 && tok.pos().line > pos.line {
+            return i;
+        }
+        if tok.is("[") {
+            depth += 1;
+        } else if tok.is("]") && depth > 0 {
+            depth -= 1;
+        }
+    }
+    tokens.len()
+}
+
+/// Finds problems in the program without running it, ordered by position.
+pub fn check(tokens: &[Token]) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        signs: signatures().map(|(p, n)| (p.to_string(), n)).collect(),
+        outputs: HashSet::new(),
+        bound: HashSet::from(["repcount".to_string()]),
+        binds_any: false,
+        vars: vec![],
+        diags: vec![],
+    };
+    checker.definitions(tokens);
+    checker.block(tokens);
+    if !checker.binds_any {
+        for (name, pos) in std::mem::take(&mut checker.vars) {
+            if !checker.bound.contains(&fold_case(&name)) {
+                checker.report(pos, LogoError::NoValue(name));
+            }
+        }
+    }
+    checker.diags.sort_by_key(|d| d.pos);
+    checker.diags
+}
@@ -0,0 +1,169 @@
+use crate::interpretator::value::Value;
+use crate::tokenizer::Pos;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+#[derive(Debug)]
+pub enum LogoError {
+    Lex(String),
+    DoesntLike {
+        proc: String,
+        input: Value,
+    },
+    NoValue(String),
+    UnknownProcedure {
+        name: String,
+        suggestion: Option<String>,
+    },
+    NotEnoughInputs(String),
+    TooManyInputs(String),
+    Unexpected(String),
+    MissingEnd(String),
+    /// A procedure is defined with the name of a primitive.
+    IsPrimitive(String),
+    UnusedValue(Value),
+    /// Reading or writing a file failed.
+    Io(String),
+    /// The error happened at the given place in the source.
+    At(Pos, Box<LogoError>),
+    /// The error escaped from a call of the user procedure.
+    In(String, Box<LogoError>),
+}
+
+impl LogoError {
+    pub fn doesnt_like(proc: &str, input: Value) -> LogoError {
+        LogoError::DoesntLike {
+            proc: proc.to_string(),
+            input,
+        }
+    }
+
+    /// `name` is not defined; suggest the closest of the `known` names.
+    pub fn unknown<'a>(name: &str, known: impl Iterator<Item = &'a String>) -> LogoError {
+        let suggestion = known
+            .map(|k| (distance(name, k), k))
+            .filter(|(d, k)| *d <= 2 && *d < k.chars().count())
+            .min()
+            .map(|(_, k)| k.clone());
+        LogoError::UnknownProcedure {
+            name: name.to_string(),
+            suggestion,
+        }
+    }
+
+    pub fn at(self, pos: Pos) -> LogoError {
+        match self {
+            e @ LogoError::At(_, _) => e,
+            e => LogoError::At(pos, Box::new(e)),
+        }
+    }
+
+    pub fn called_from(self, proc: &str) -> LogoError {
+        LogoError::In(proc.to_string(), Box::new(self))
+    }
+
+    /// The innermost error, without position and call chain.
+    pub fn cause(&self) -> &LogoError {
+        match self {
+            LogoError::At(_, e) | LogoError::In(_, e) => e.cause(),
+            e => e,
+        }
+    }
+
+    /// Where the innermost error happened, if known.
+    pub fn pos(&self) -> Option<Pos> {
+        match self {
+            LogoError::In(_, e) => e.pos(),
+            LogoError::At(pos, e) => e.pos().or(Some(*pos)),
+            _ => None,
+        }
+    }
+
+    /// User procedures the error escaped from, innermost first.
+    pub fn backtrace(&self) -> Vec<&str> {
+        match self {
+            LogoError::At(_, e) => e.backtrace(),
+            LogoError::In(proc, e) => {
+                let mut trace = e.backtrace();
+                trace.push(proc);
+                trace
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Full report with the file position and the chain of procedure calls.
+    pub fn report(&self, file: &str) -> String {
+        let mut out = self.cause().to_string();
+        if let Some(pos) = self.pos() {
+            out += &format!("\n  at {file}:{pos}");
+        }
+        let trace = self.backtrace();
+        if !trace.is_empty() {
+            let mut calls: Vec<String> = vec![];
+            let mut i = 0;
+            while i < trace.len() {
+                let n = trace[i..].iter().take_while(|p| **p == trace[i]).count();
+                calls.push(match n {
+                    1 => trace[i].to_string(),
+                    n => format!("{} ({n} times)", trace[i]),
+                });
+                i += n;
+            }
+            out += &format!("\n  in {}", calls.join(" called from "));
+        }
+        out
+    }
+}
+
+impl Display for LogoError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use LogoError::*;
+        match self {
+            Lex(msg) => write!(formatter, "{msg}"),
+            DoesntLike { proc, input } => {
+                write!(formatter, "{proc} doesn't like {} as input", input.show())
+            }
+            NoValue(name) => write!(formatter, "{name} has no value"),
+            UnknownProcedure { name, suggestion } => {
+                write!(formatter, "I don't know how to {name}")?;
+                match suggestion {
+                    Some(s) => write!(formatter, "; did you mean {s}?"),
+                    None => Ok(()),
+                }
+            }
+            NotEnoughInputs(proc) => write!(formatter, "not enough inputs to {proc}"),
+            TooManyInputs(proc) => write!(formatter, "too many inputs to {proc}"),
+            Unexpected(txt) => write!(formatter, "unexpected {txt}"),
+            MissingEnd(proc) => write!(formatter, "missing 'end' of {proc}"),
+            IsPrimitive(proc) => write!(formatter, "{proc} is a primitive"),
+            UnusedValue(v) => write!(formatter, "You don't say what to do with {}", v.show()),
+            Io(msg) => write!(formatter, "{msg}"),
+            At(pos, e) => write!(formatter, "{e} at {pos}"),
+            In(proc, e) => write!(formatter, "{e} in {proc}"),
+        }
+    }
+}
+
+impl std::error::Error for LogoError {}
+
+/// Levenshtein distance used for the did-you-mean hints.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(cur)
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,290 @@
+use crate::error::LogoError;
+use crate::interpretator::primitives::canonical;
+use crate::interpretator::primitives::signatures;
+use crate::interpretator::value::Value;
+use crate::parser::fold_case;
+use crate::parser::parse_statement;
+use crate::parser::scan_definitions;
+use crate::parser::Exp;
+use crate::parser::Procedure;
+use crate::parser::Stat;
+use crate::tokenizer::escape;
+use crate::tokenizer::tokenize_with_comments;
+use crate::tokenizer::Kind;
+use crate::tokenizer::Pos;
+use crate::tokenizer::Token;
+use crate::unsee::Unsee;
+use std::collections::HashMap;
+
+const INDENT: &str = "  ";
+
+/// Inputs of primitives which are run as code rather than used as data.
+fn is_code(key: &str, input: usize) -> bool {
+    matches!((key, input), ("repeat" | "if" | "filled", 1) | ("run", 0))
+}
+
+/// Whether `e` is written as a procedure taking inputs, which would take
+/// in the operators following it too.
+fn is_call(e: &Exp) -> bool {
+    match e {
+        Exp::Call(pr, args, _) if fold_case(pr) == "minus" => is_call(&args[0]),
+        Exp::Call(_, args, _) => !args.is_empty(),
+        _ => false,
+    }
+}
+
+fn paren(s: String) -> String {
+    format!("({s})")
+}
+
+/// Source of a token in a list used as data.
+fn token(tok: &Token) -> String {
+    match tok.kind {
+        Kind::Word => format!("\"{}", escape(&tok.text[1..], false)),
+        Kind::Var => format!(":{}", escape(&tok.text[1..], true)),
+        Kind::Name => escape(&tok.text, true),
+        _ => tok.text.clone(),
+    }
+}
+
+struct Printer<'a> {
+    signs: HashMap<String, usize>,
+    /// Procedure names as written in their definitions.
+    names: HashMap<String, String>,
+    /// All the tokens, to find the brackets around lists.
+    tokens: &'a [Token],
+    comments: &'a [Token],
+    /// Index of the next comment to print.
+    next: usize,
+    /// Last line of the source printed so far, to keep blank lines.
+    line: usize,
+}
+
+impl Printer<'_> {
+    fn name(&self, pr: &str) -> String {
+        let key = fold_case(pr);
+        if let Some(name) = canonical(&key) {
+            return name.to_string();
+        }
+        self.names.get(&key).cloned().unwrap_or(key)
+    }
+
+    /// Index of the first token starting at `pos` or after it.
+    fn find(&self, pos: Pos) -> usize {
+        self.tokens.partition_point(|t| t.span.start < pos)
+    }
+
+    fn comment_before(&self, pos: Pos) -> Option<&Token> {
+        self.comments.get(self.next).filter(|c| c.pos() < pos)
+    }
+
+    /// Starts a line for something from `line` of the source, keeping one
+    /// blank line where the source had any.
+    fn newline(&mut self, out: &mut String, line: usize, indent: usize, first: bool) {
+        if !first && line > self.line + 1 {
+            out.push('\n');
+        }
+        self.line = line;
+        out.push_str(&INDENT.repeat(indent));
+    }
+
+    /// Comments before `pos`, each on its own line.
+    fn comments(&mut self, out: &mut String, pos: Pos, indent: usize, first: &mut bool) {
+        while let Some(c) = self.comment_before(pos) {
+            let (line, text) = (c.pos().line, c.text.trim_end().to_string());
+            self.newline(out, line, indent, *first);
+            out.push_str(&text);
+            out.push('\n');
+            self.next += 1;
+            *first = false;
+        }
+    }
+
+    /// A comment after the end of a statement on its last line.
+    fn trailing(&mut self, out: &mut String, end: Pos, before: Pos) {
+        if let Some(c) = self.comment_before(before) {
+            if c.pos().line == end.line && c.pos() >= end {
+                out.push(' ');
+                out.push_str(c.text.trim_end());
+                self.next += 1;
+            }
+        }
+    }
+
+    fn statements(&self, tokens: &[Token]) -> Result<Vec<(Stat, usize, usize)>, LogoError> {
+        let mut stats = vec![];
+        let mut at = 0;
+        loop {
+            let mut iter = Unsee::wrap(tokens[at..].iter());
+            let Some(stat) = parse_statement(&self.signs, &mut iter)? else {
+                return Ok(stats);
+            };
+            let next = at + iter.consumed();
+            stats.push((stat, at, next));
+            at = next;
+        }
+    }
+
+    /// Lines of the statements in `tokens`, followed by the comments before
+    /// `end`.
+    fn block(&mut self, tokens: &[Token], indent: usize, end: Pos) -> Result<String, LogoError> {
+        let mut out = String::new();
+        let mut first = true;
+        for (stat, at, next) in self.statements(tokens)? {
+            let start = tokens[at].pos();
+            self.comments(&mut out, start, indent, &mut first);
+            self.newline(&mut out, start.line, indent, first);
+            first = false;
+            out += &self.stat(&stat, start, indent)?;
+            let stat_end = tokens[next - 1].span.end;
+            let before = tokens.get(next).map_or(end, Token::pos);
+            self.trailing(&mut out, stat_end, before);
+            self.line = self.line.max(stat_end.line);
+            out.push('\n');
+        }
+        self.comments(&mut out, end, indent, &mut first);
+        Ok(out)
+    }
+
+    fn stat(&mut self, stat: &Stat, start: Pos, indent: usize) -> Result<String, LogoError> {
+        match stat {
+            Stat::ProcDef(proc) => self.procedure(proc, start, indent),
+            Stat::Exp(e) => self.exp(e, indent),
+        }
+    }
+
+    fn procedure(
+        &mut self,
+        proc: &Procedure,
+        start: Pos,
+        indent: usize,
+    ) -> Result<String, LogoError> {
+        let mut out = format!("to {}", self.name(proc.get_name()));
+        for v in proc.get_argv() {
+            out += &format!(" :{}", escape(v, true));
+        }
+        let body = proc.get_body().tokens();
+        // `to`, the name and the inputs come before the body.
+        let i = self.find(start) + 2 + proc.signature() + body.len();
+        let end = self.tokens.get(i).map_or(start, Token::pos);
+        let first = body.first().map_or(end, Token::pos);
+        let header_end = Pos {
+            line: start.line,
+            column: usize::MAX,
+        };
+        self.trailing(&mut out, start, first.min(header_end));
+        out.push('\n');
+        out += &self.block(body, indent + 1, end)?;
+        out += &INDENT.repeat(indent);
+        out += "end";
+        self.line = end.line;
+        Ok(out)
+    }
+
+    /// A list run as code: a single statement stays on the line of the
+    /// procedure it is an input of, more go on lines of their own.
+    fn code(&mut self, tokens: &[Token], indent: usize) -> Result<String, LogoError> {
+        let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
+            return Ok("[ ]".to_string());
+        };
+        let close = self.tokens.get(self.find(last.span.end));
+        let close = close.map_or(last.span.end, Token::pos);
+        let stats = self.statements(tokens)?;
+        if let [(stat, _, _)] = &stats[..] {
+            let line = self.line;
+            let next = self.next;
+            if self.comment_before(close).is_none() {
+                let s = self.stat(stat, first.pos(), indent)?;
+                if !s.contains('\n') {
+                    return Ok(format!("[ {s} ]"));
+                }
+            }
+            (self.line, self.next) = (line, next);
+        }
+        let mut out = "[\n".to_string();
+        out += &self.block(tokens, indent + 1, close)?;
+        out += &INDENT.repeat(indent);
+        out += "]";
+        self.line = close.line;
+        Ok(out)
+    }
+
+    fn exp(&mut self, e: &Exp, indent: usize) -> Result<String, LogoError> {
+        Ok(match e {
+            Exp::Call(pr, args, _) if fold_case(pr) == "minus" && !is_call(e) => match &args[0] {
+                Exp::Oper(..) => format!("-({})", self.exp(&args[0], indent)?),
+                x => format!("-{}", self.exp(x, indent)?),
+            },
+            Exp::Call(pr, args, _) => {
+                let key = fold_case(pr);
+                let mut out = self.name(pr);
+                for (i, arg) in args.iter().enumerate() {
+                    out.push(' ');
+                    out += &match arg {
+                        // A list which doesn't parse is left as it is.
+                        Exp::Const(Value::List(code)) if is_code(&key, i) => {
+                            match self.code(code.tokens(), indent) {
+                                Ok(s) => s,
+                                Err(_) => self.exp(arg, indent)?,
+                            }
+                        }
+                        arg => self.exp(arg, indent)?,
+                    };
+                }
+                out
+            }
+            Exp::Oper(op, x, y, _) => {
+                let mut l = self.exp(x, indent)?;
+                let mut r = self.exp(y, indent)?;
+                if is_call(x) {
+                    l = paren(l);
+                }
+                if is_call(y) || matches!(**y, Exp::Oper(..)) {
+                    r = paren(r);
+                }
+                format!("{l} {} {r}", op.symbol())
+            }
+            Exp::Const(Value::Str(s)) => format!("\"{}", escape(s, false)),
+            Exp::Const(Value::List(code)) => {
+                let mut out = "[".to_string();
+                let mut glue = false;
+                for tok in code.tokens() {
+                    if !glue {
+                        out.push(' ');
+                    }
+                    out += &token(tok);
+                    glue = tok.kind == Kind::Neg;
+                }
+                out + " ]"
+            }
+            Exp::Const(v) => v.to_string(),
+            Exp::Var(name, _) if name == "repcount" => name.clone(),
+            Exp::Var(name, _) => format!(":{}", escape(name, true)),
+        })
+    }
+}
+
+/// The program in `src` laid out canonically.
+pub fn format(src: &str) -> Result<String, LogoError> {
+    let (tokens, comments) = tokenize_with_comments(src)?;
+    let mut signs: HashMap<String, usize> = signatures().map(|(p, n)| (p.to_string(), n)).collect();
+    let mut names = HashMap::new();
+    for def in scan_definitions(&tokens) {
+        let key = fold_case(&def.name.text);
+        signs.entry(key.clone()).or_insert(def.argv.len());
+        names.entry(key).or_insert(def.name.text.clone());
+    }
+    let mut printer = Printer {
+        signs,
+        names,
+        tokens: &tokens,
+        comments: &comments,
+        next: 0,
+        line: 0,
+    };
+    let end = Pos {
+        line: usize::MAX,
+        column: 0,
+    };
+    printer.block(&tokens, 0, end)
+}
@@ -1,82 +1,194 @@
+use crate::error::LogoError;
 use crate::interpretator::Value::*;
-use crate::parser::parse_statement;
+use crate::parser::code::Code;
+use crate::parser::code::Statement;
+use crate::parser::fold_case;
 use crate::parser::Exp;
 use crate::parser::Procedure;
 use crate::parser::Stat;
 use crate::parser::OP;
+use crate::robot::animation::AnimationOptions;
+use crate::robot::canvas::Canvas;
+use crate::robot::canvas::Recording;
+use crate::robot::gif_canvas::GifOptions;
+use crate::robot::pdf::PdfOptions;
+use crate::robot::raster::PngOptions;
 use crate::robot::Robot;
-use crate::unsee::Unsee;
-use rand::seq::SliceRandom;
-use rand::Rng;
+use crate::robot::SaveOptions;
+use crate::tokenizer::Pos;
+use crate::tokenizer::Token;
 use std::collections::HashMap;
-use std::collections::VecDeque;
-use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
 use std::iter::zip;
+use std::path::Path;
+use std::sync::Arc;
+pub mod bytecode;
+pub mod optimize;
+pub mod primitives;
 pub mod value;
+pub mod vm;
+use primitives::Handler;
+use primitives::Primitive;
 use value::*;
 
+/// How expression statements are evaluated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Engine {
+    /// Walk the parsed expression trees.
+    #[default]
+    Tree,
+    /// Compile procedure bodies and lists, with the `repeat` and `if` lists
+    /// in them, to bytecode and run it on a stack machine. A body is parsed
+    /// whole before it runs.
+    Vm,
+}
+
+/// Settings of an interpreter. More may come, so start from `default()`.
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub struct Config {
+    pub engine: Engine,
+    /// Run the `optimize` pass over statements as they are parsed.
+    pub optimize: bool,
+    /// Print every statement to stderr once it is parsed.
+    pub dump_tree: bool,
+}
+
 pub struct Context {
     robot: Robot,
     vars: HashMap<String, Value>,
-    procs: HashMap<String, Procedure>,
+    procs: HashMap<String, Arc<Procedure>>,
+    primitives: HashMap<String, Handler>,
     signs: HashMap<String, usize>,
+    /// Bumped whenever an arity changes, which invalidates parsed code, and
+    /// when a procedure or primitive is replaced, as compiled calls go to
+    /// the old one and optimized code may have inlined its body.
+    generation: u64,
+    opts: Config,
+    pub stdout: Box<dyn Write + Send>,
+    pub stdin: Box<dyn BufRead + Send>,
 }
 
 impl Context {
-    pub fn new() -> Context {
+    pub fn new(opts: Config) -> Context {
         Context {
             robot: Robot::new(),
             vars: HashMap::new(),
             procs: HashMap::new(),
+            primitives: primitives::builtins(),
             signs: Self::init_signatures(),
+            generation: 0,
+            opts,
+            stdout: Box::new(io::stdout()),
+            stdin: Box::new(BufReader::new(io::stdin())),
+        }
+    }
+
+    /// Defines the user procedure `proc`, unless a primitive has its name.
+    fn define(&mut self, proc: &Arc<Procedure>) -> Result<(), LogoError> {
+        let name = fold_case(proc.get_name());
+        if self.is_primitive(&name) {
+            return Err(LogoError::IsPrimitive(proc.get_name().to_string()));
+        }
+        let old = self.signs.insert(name.clone(), proc.signature());
+        let replaced = self.procs.insert(name, proc.clone());
+        if old.is_some_and(|n| n != proc.signature())
+            || replaced.is_some_and(|p| !Arc::ptr_eq(&p, proc))
+        {
+            self.generation += 1;
         }
+        Ok(())
+    }
+
+    /// Changes whenever parsed code has to be parsed again.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
     }
 
-    pub fn plot(&mut self) -> svg::Document {
+    pub fn plot(&self) -> svg::Document {
         self.robot.plot()
     }
 
+    pub fn recording(&self) -> &Recording {
+        self.robot.recording()
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas) {
+        self.robot.draw(canvas)
+    }
+
+    pub fn animation(&self, options: AnimationOptions) -> svg::Document {
+        self.robot.animation(options)
+    }
+
+    pub fn png(&self, options: PngOptions) -> Result<Vec<u8>, LogoError> {
+        self.robot.png(options)
+    }
+
+    pub fn pdf(&self, options: PdfOptions) -> Vec<u8> {
+        self.robot.pdf(options)
+    }
+
+    pub fn gif(&self, options: GifOptions) -> Result<Vec<u8>, LogoError> {
+        self.robot.gif(options)
+    }
+
+    /// Saves the drawing in the format named by the extension of `file`:
+    /// `.png`, `.pdf`, `.gif` or SVG for any other.
+    pub fn save(&self, file: &str, options: SaveOptions) -> Result<(), LogoError> {
+        let ext = Path::new(file).extension().unwrap_or_default();
+        let ext = ext.to_string_lossy().to_lowercase();
+        let saved = match &ext[..] {
+            "png" => fs::write(file, self.png(options.png)?),
+            "pdf" => fs::write(file, self.pdf(options.pdf)),
+            "gif" => fs::write(file, self.gif(options.gif)?),
+            _ => match options.animation {
+                Some(animation) => svg::save(file, &self.animation(animation)),
+                None => svg::save(file, &self.plot()),
+            },
+        };
+        saved.map_err(|e| LogoError::Io(format!("{file}: {e}")))
+    }
+
+    /// Names of the primitives and of the procedures defined so far.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.signs.keys()
+    }
+
     fn init_signatures() -> HashMap<String, usize> {
-        let mut signs: HashMap<String, usize> = HashMap::new();
-        signs.insert("stop".to_string(), 0);
-        signs.insert("output".to_string(), 1);
-        signs.insert("fd".to_string(), 1);
-        signs.insert("forward".to_string(), 1);
-        signs.insert("bk".to_string(), 1);
-        signs.insert("back".to_string(), 1);
-        signs.insert("rt".to_string(), 1);
-        signs.insert("right".to_string(), 1);
-        signs.insert("lt".to_string(), 1);
-        signs.insert("left".to_string(), 1);
-        signs.insert("setcolor".to_string(), 1);
-        signs.insert("setpencolor".to_string(), 1);
-        signs.insert("home".to_string(), 0);
-        signs.insert("label".to_string(), 1);
-        signs.insert("setlabelheight".to_string(), 1);
-        signs.insert("penup".to_string(), 0);
-        signs.insert("pu".to_string(), 0);
-        signs.insert("pendown".to_string(), 0);
-        signs.insert("pd".to_string(), 0);
-        signs.insert("wait".to_string(), 1);
-        signs.insert("clean".to_string(), 0);
-        signs.insert("clearscreen".to_string(), 0);
-        signs.insert("cs".to_string(), 0);
-        signs.insert("window".to_string(), 0);
-        signs.insert("hideturtle".to_string(), 0);
-        signs.insert("ht".to_string(), 0);
-        signs.insert("showturtle".to_string(), 0);
-        signs.insert("st".to_string(), 0);
-        signs.insert("pr".to_string(), 1);
-        signs.insert("print".to_string(), 1);
-        signs.insert("repeat".to_string(), 2);
-        signs.insert("if".to_string(), 2);
-        signs.insert("make".to_string(), 2);
-
-        signs.insert("pick".to_string(), 1);
-        signs.insert("random".to_string(), 1);
-        signs.insert("sentence".to_string(), 2);
-
-        signs
+        primitives::signatures()
+            .map(|(name, n)| (name.to_string(), n))
+            .collect()
+    }
+
+    /// Adds a primitive, which replaces any procedure of the same name.
+    pub fn register(&mut self, primitive: Arc<dyn Primitive>) {
+        for name in primitive.names() {
+            let name = fold_case(name);
+            if self.signs.insert(name.clone(), primitive.arity()).is_some() {
+                self.generation += 1;
+            }
+            self.primitives
+                .insert(name, Handler::Native(primitive.clone()));
+        }
+    }
+
+    /// Whether `key` names a primitive, which user procedures can't
+    /// replace.
+    fn is_primitive(&self, key: &str) -> bool {
+        self.primitives.contains_key(key)
+    }
+
+    pub fn var(&self, name: &str) -> Option<&Value> {
+        self.vars.get(&fold_case(name))
+    }
+
+    pub fn set_var(&mut self, name: &str, v: Value) {
+        self.vars.insert(fold_case(name), v);
     }
 
     fn keep_values_out_of_context(&mut self, names: Vec<String>) -> Vec<(String, Option<Value>)> {
@@ -104,11 +216,13 @@ enum ExpResult {
     Outcome(Value),
 }
 
+type Res = Result<ExpResult, LogoError>;
+
 impl ExpResult {
-    fn and_then<F: FnOnce(Value) -> ExpResult>(self, f: F) -> ExpResult {
+    fn and_then<F: FnOnce(Value) -> Res>(self, f: F) -> Res {
         match self {
             ExpResult::Outcome(x) => f(x),
-            exit => exit,
+            exit => Ok(exit),
         }
     }
 
@@ -120,193 +234,197 @@ impl ExpResult {
     }
 }
 
-fn interete_exp(ctx: &mut Context, exp: Exp) -> ExpResult {
+fn io_error(e: io::Error) -> LogoError {
+    LogoError::Io(e.to_string())
+}
+
+/// A line of the input without its line break, `None` at the end.
+fn read_line(ctx: &mut Context) -> Result<Option<String>, LogoError> {
+    let mut line = String::new();
+    if ctx.stdin.read_line(&mut line).map_err(io_error)? == 0 {
+        return Ok(None);
+    }
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+    Ok(Some(line))
+}
+
+fn number(pr: &str, v: Value) -> Result<f32, LogoError> {
+    v.as_num().ok_or_else(|| LogoError::doesnt_like(pr, v))
+}
+
+fn list(pr: &str, v: Value) -> Result<Arc<Code>, LogoError> {
+    match v {
+        List(l) => Ok(l),
+        v => Err(LogoError::doesnt_like(pr, v)),
+    }
+}
+
+fn interete_exp(ctx: &mut Context, exp: &Exp) -> Res {
     use crate::parser::Exp::*;
     match exp {
-        Oper(m, e1, e2) => interete_exp(ctx, *e1).and_then(|x| {
-            interete_exp(ctx, *e2).and_then(|y| match m {
-                OP::Sub => ExpResult::Outcome(x - y),
-                OP::Mul => ExpResult::Outcome(x * y),
-                OP::Div => ExpResult::Outcome(x / y),
-                OP::Le => ExpResult::Outcome(Value::from(x < y)),
+        Oper(m, e1, e2, span) => interete_exp(ctx, e1)?.and_then(|x| {
+            interete_exp(ctx, e2)?.and_then(|y| {
+                let v = match m {
+                    OP::Sub => x - y,
+                    OP::Mul => x * y,
+                    OP::Div => x / y,
+                    OP::Le => x.less(y),
+                };
+                v.map(ExpResult::Outcome).map_err(|e| e.at(span.start))
             })
         }),
-        Call(pr, args) => interpretr_call(ctx, pr, args),
-        Const(v) => ExpResult::Outcome(v),
-        Var(s) => ExpResult::Outcome(ctx.vars.get(&s).cloned().unwrap()),
+        Call(pr, args, span) => interpretr_call(ctx, pr, args).map_err(|e| e.at(span.start)),
+        Const(v) => Ok(ExpResult::Outcome(v.clone())),
+        Var(s, span) => match ctx.vars.get(&fold_case(s)) {
+            Some(v) => Ok(ExpResult::Outcome(v.clone())),
+            None => Err(LogoError::NoValue(s.clone()).at(span.start)),
+        },
     }
 }
 
-fn interprete_run(ctx: &mut Context, code: Value) -> ExpResult {
-    let code: Vec<String> = code.try_into().expect("Expect List!");
-    let mut unsee = Unsee::wrap(code.iter().map(AsRef::as_ref));
-    interete(ctx, &mut unsee)
+fn interprete_run(ctx: &mut Context, pr: &str, code: Value) -> Res {
+    let code = list(pr, code)?;
+    interete(ctx, &code)
 }
 
-fn interpretr_proc(ctx: &mut Context, proc: Procedure, vals: VecDeque<Value>) -> ExpResult {
-    let argv: Vec<String> = proc.get_argv().into_iter().map(str::to_owned).collect();
+/// Values of variables hidden by the inputs of a procedure while it runs.
+type Hidden = Vec<(String, Option<Value>)>;
+
+/// Gives the inputs of `proc` their values, hiding those they had, which
+/// are returned to be restored once it is done.
+fn bind_inputs(ctx: &mut Context, proc: &Procedure, vals: Vec<Value>) -> Hidden {
+    let argv: Vec<String> = proc.get_argv().into_iter().map(fold_case).collect();
     let save = ctx.keep_values_out_of_context(argv.clone());
 
     zip(argv, vals).for_each(|(name, val)| {
         ctx.vars.insert(name, val);
     });
-    let res = interete(ctx, &mut Unsee::wrap(proc.get_body().into_iter()));
+    save
+}
+
+fn interpretr_proc(ctx: &mut Context, proc: Arc<Procedure>, vals: Vec<Value>) -> Res {
+    let save = bind_inputs(ctx, &proc, vals);
+    let res = interete(ctx, proc.get_body());
 
     ctx.restore_values_from_context(save);
-    res.exp_return()
+    res.map(ExpResult::exp_return)
+        .map_err(|e| e.called_from(proc.get_name()))
 }
 
-fn interpretr_call(ctx: &mut Context, pr: String, args: Vec<Exp>) -> ExpResult {
-    let mut vals: VecDeque<Value> = VecDeque::new();
-    for e in args.into_iter() {
-        match interete_exp(ctx, e) {
-            ExpResult::Exit(v) => return ExpResult::Exit(v),
-            ExpResult::Outcome(r) => vals.push_back(r),
+fn interpretr_call(ctx: &mut Context, pr: &str, args: &[Exp]) -> Res {
+    let mut vals: Vec<Value> = Vec::with_capacity(args.len());
+    for e in args {
+        match interete_exp(ctx, e)? {
+            ExpResult::Exit(v) => return Ok(ExpResult::Exit(v)),
+            ExpResult::Outcome(r) => vals.push(r),
         }
     }
-    match &pr[..] {
-        "stop" => return ExpResult::Exit(Value::Void),
-        "output" => return ExpResult::Exit(vals.pop_front().unwrap()),
-        _ => (),
+    apply(ctx, pr, &fold_case(pr), vals)
+}
+
+/// Calls the primitive or user procedure `pr`, looked up by `key`, with
+/// already evaluated inputs.
+fn apply(ctx: &mut Context, pr: &str, key: &str, vals: Vec<Value>) -> Res {
+    if let Some(handler) = ctx.primitives.get(key) {
+        return handler.clone().call(ctx, pr, vals);
     }
-    match &pr[..] {
-        "fd" | "forward" => ctx.robot.forward(
-            vals.pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected number!"),
-        ),
-        "bk" | "back" => ctx.robot.back(
-            vals.pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected number!"),
-        ),
-        "rt" | "right" => {
-            let d: f32 = vals
-                .pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected number!");
-            ctx.robot.right(d * PI / 180.0)
-        }
+    let proc = ctx
+        .procs
+        .get(key)
+        .cloned()
+        .ok_or_else(|| LogoError::unknown(pr, ctx.signs.keys()))?;
+    interpretr_proc(ctx, proc, vals)
+}
 
-        "lt" | "left" => {
-            let d: f32 = vals
-                .pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected number!");
-            ctx.robot.left(d * PI / 180.0)
-        }
-        "setcolor" | "setpencolor" => ctx.robot.setpencolor(vals.pop_front().unwrap().to_string()),
-        "home" => ctx.robot.home(),
-        "label" => ctx.robot.label(vals.pop_front().unwrap().to_string()),
-        "setlabelheight" => ctx.robot.setlabelheight(
-            vals.pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected number!"),
-        ),
-        "penup" | "pu" => ctx.robot.penup(),
-        "pendown" | "pd" => ctx.robot.pendown(),
-        "wait" => println!("wait {:?}", vals.pop_front().unwrap()),
-        "clean" => ctx.robot.clean(),
-        "clearscreen" | "cs" => ctx.robot.clearscreen(),
-        "window" => println!("window"),
-        "hideturtle" | "ht" => println!("Hide the turtle!"),
-        "showturtle" | "st" => println!("Show the turtle!"),
-        "pick" => {
-            let vs: Vec<String> = vals.pop_front().unwrap().try_into().expect("Expect List!");
-            return ExpResult::Outcome(Value::Str(
-                vs.choose(&mut rand::thread_rng()).unwrap().clone(),
-            ));
-        }
-        "random" => {
-            let n: f32 = vals
-                .pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected number!");
-            let n: i32 = n as i32;
-            return ExpResult::Outcome(Value::Num(rand::thread_rng().gen_range(0..n) as f32));
-        }
-        "sentence" => {
-            let mut l1: Vec<String> = vals.pop_front().unwrap().try_into().expect("Expect List!");
-            let mut l2: Vec<String> = vals.pop_front().unwrap().try_into().expect("Expect List!");
-            l1.append(&mut l2);
-            return ExpResult::Outcome(Value::List(l1));
-        }
-        "pr" | "print" => {
-            println!("{}", vals[0]);
-        }
-        "run" => {
-            return interprete_run(ctx, vals.pop_front().unwrap());
-        }
+fn interete(ctx: &mut Context, code: &Arc<Code>) -> Res {
+    match ctx.opts.engine {
+        Engine::Tree => run_rest(ctx, code, 0, 0),
+        Engine::Vm => vm::exec(ctx, &code.chunk(ctx)),
+    }
+}
 
-        "repeat" => {
-            let num: f32 = vals
-                .pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected number!");
-            let num: i32 = num as i32;
-            let code = vals.pop_front().unwrap();
-            for i in 0..num {
-                ctx.vars.insert("repcount".to_string(), Num(i as f32));
-                let v = interprete_run(ctx, code.clone());
-                if let ExpResult::Exit(res) = v {
-                    return ExpResult::Exit(res);
-                }
-            }
-        }
-        "if" => {
-            let que = vals
-                .pop_front()
-                .unwrap()
-                .try_into()
-                .expect("Expected boolean!");
-            let code = vals.pop_front().unwrap();
-            if que {
-                return interprete_run(ctx, code);
-            }
-        }
-        "make" => {
-            let name: String = vals.pop_front().unwrap().to_string();
-            ctx.vars.insert(name, vals.pop_front().unwrap());
-        }
-        s => {
-            let proc = ctx
-                .procs
-                .get(s)
-                .cloned()
-                .unwrap_or_else(|| panic!("Unknown command: {s}"));
-            return interpretr_proc(ctx, proc, vals);
+/// Runs the statements of `code` from the `i`-th, which starts at token
+/// `at`. Values left over by them are errors.
+fn run_rest(ctx: &mut Context, code: &Code, i: usize, at: usize) -> Res {
+    run_statements(ctx, code, i, at, |v, start| match v {
+        Value::Void => Ok(()),
+        v => Err(LogoError::UnusedValue(v).at(start)),
+    })
+}
+
+/// The `i`-th statement of `code`, which starts at token `at`, parsed for
+/// the signatures `ctx` has now.
+fn statement(
+    ctx: &Context,
+    code: &Code,
+    i: usize,
+    at: usize,
+) -> Result<Option<Arc<Statement>>, LogoError> {
+    let prepare = |stat| {
+        let stat = match ctx.opts.optimize {
+            true => optimize::stat(ctx, stat),
+            false => stat,
+        };
+        if ctx.opts.dump_tree {
+            let start = code.tokens().get(at).map(Token::pos).unwrap_or_default();
+            eprintln!("{start}: {stat}");
         }
+        stat
     };
-    ExpResult::Outcome(Value::Void)
+    code.statement(&ctx.signs, ctx.generation, i, at, prepare)
 }
 
-fn interete(ctx: &mut Context, iter: &mut Unsee<&str>) -> ExpResult {
-    loop {
-        match parse_statement(&ctx.signs, iter) {
-            None => return ExpResult::Outcome(Value::Void),
-            Some(Stat::ProcDef(proc)) => {
-                ctx.signs
-                    .insert(proc.get_name().to_owned(), proc.signature());
-                ctx.procs.insert(proc.get_name().to_owned(), proc);
+/// Runs the statements of `code` from the `i`-th, which starts at token
+/// `at`, giving the value of each expression statement to `done`.
+fn run_statements(
+    ctx: &mut Context,
+    code: &Code,
+    i: usize,
+    mut at: usize,
+    mut done: impl FnMut(Value, Pos) -> Result<(), LogoError>,
+) -> Res {
+    for i in i.. {
+        let start = code.tokens().get(at).map(Token::pos).unwrap_or_default();
+        let Some(st) = statement(ctx, code, i, at)? else {
+            break;
+        };
+        at = st.next;
+        let e = match &st.stat {
+            Stat::ProcDef(proc) => {
+                ctx.define(proc).map_err(|e| e.at(start))?;
+                continue;
             }
-            Some(Stat::Exp(e)) => match interete_exp(ctx, e) {
-                ExpResult::Outcome(Value::Void) => continue,
-                ExpResult::Outcome(v) => panic!("Don't know what to do with {v}"),
-                ExpResult::Exit(v) => return ExpResult::Exit(v),
-            },
+            Stat::Exp(e) => e,
+        };
+        let res = match ctx.opts.engine {
+            Engine::Tree => interete_exp(ctx, e)?,
+            Engine::Vm => vm::exec(ctx, st.chunk(e, ctx))?,
+        };
+        match res {
+            ExpResult::Outcome(v) => done(v, start)?,
+            ExpResult::Exit(v) => return Ok(ExpResult::Exit(v)),
         }
     }
+    Ok(ExpResult::Outcome(Value::Void))
+}
+
+/// Runs a program. Values left over by its statements are errors.
+pub fn run(ctx: &mut Context, data: Vec<Token>) -> Result<(), LogoError> {
+    run_rest(ctx, &Code::new(data), 0, 0)?;
+    Ok(())
 }
 
-pub fn inter(data: Vec<&str>) -> svg::Document {
-    let mut ctx = Context::new();
-    interete(&mut ctx, &mut Unsee::wrap(data.iter().map(AsRef::as_ref)));
-    ctx.plot()
+/// Runs `data` and outputs the value left over by its last statement. Only
+/// the last one may leave a value.
+pub fn eval(ctx: &mut Context, data: Vec<Token>) -> Result<Value, LogoError> {
+    let mut last: Option<(Value, Pos)> = None;
+    run_statements(ctx, &Code::new(data), 0, 0, |v, start| {
+        if let Some((v, pos)) = last.take().filter(|(v, _)| *v != Value::Void) {
+            return Err(LogoError::UnusedValue(v).at(pos));
+        }
+        last = Some((v, start));
+        Ok(())
+    })?;
+    Ok(last.map_or(Value::Void, |(v, _)| v))
 }
@@ -0,0 +1,269 @@
+use crate::interpretator::primitives::Handler;
+use crate::interpretator::statement;
+use crate::interpretator::value::Value;
+use crate::interpretator::Context;
+use crate::parser::code::Code;
+use crate::parser::fold_case;
+use crate::parser::Exp;
+use crate::parser::Procedure;
+use crate::parser::Stat;
+use crate::parser::OP;
+use crate::tokenizer::Span;
+use std::sync::Arc;
+
+/// One instruction of the stack machine. Operands index the constant,
+/// name, call and resume tables of the chunk, or are where in `ops` to
+/// jump to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Op {
+    /// Push a constant.
+    Const(u32),
+    /// Push the value of a variable.
+    Load(u32),
+    /// Pop two values and push the result of the operator.
+    Sub,
+    Mul,
+    Div,
+    Le,
+    /// Pop `argc` inputs, call the procedure and push its result.
+    Call(u32, u32),
+    /// Pop the value of a statement, which must be `Void`.
+    Pop,
+    /// Resume at a statement with the tree walker if a procedure was
+    /// defined since the chunk was compiled, as it may now parse otherwise.
+    Check(u32),
+    /// Resume at a statement the chunk couldn't compile, a definition or
+    /// one which doesn't parse, with the tree walker.
+    Rest(u32),
+    /// Pop the count of an inlined `repeat` of the call and start looping,
+    /// or jump over the list.
+    Repeat(u32, u32),
+    /// Loop back to the start of the list of the innermost `repeat`, which
+    /// is resumed as the list is now parsed if a procedure was defined.
+    Next(u32),
+    /// Pop the condition of an inlined `if` of the call and jump over the
+    /// list if it is false.
+    If(u32, u32),
+}
+
+/// What a call runs, found when the chunk is compiled. Chunks are dropped
+/// with the parsed code when the `generation` changes, which it does
+/// whenever a procedure or primitive is replaced.
+#[derive(Clone)]
+pub(super) enum Callee {
+    Primitive(Handler),
+    Proc(Arc<Procedure>),
+}
+
+/// A call as written in the source and what it runs, `None` for a name
+/// unknown when the chunk was compiled.
+pub(super) struct Call {
+    pub text: String,
+    pub callee: Option<Callee>,
+}
+
+/// A name as written in the source and the key it is looked up by.
+pub struct Name {
+    pub text: String,
+    pub key: String,
+}
+
+/// Where the statements of `code` are run from the `i`-th, which starts at
+/// token `at`, going on at `end` once they are done.
+pub(super) struct Resume {
+    pub code: Arc<Code>,
+    pub i: usize,
+    pub at: usize,
+    pub end: usize,
+}
+
+/// Bytecode of an expression statement, or of all the statements of some
+/// code with the `repeat` and `if` lists in them.
+pub struct Chunk {
+    pub ops: Vec<Op>,
+    /// Where in the source each of `ops` comes from, for error messages.
+    pub spans: Vec<Span>,
+    pub consts: Vec<Value>,
+    pub names: Vec<Name>,
+    pub(super) calls: Vec<Call>,
+    pub(super) resumes: Vec<Resume>,
+    /// Deepest the stack gets while running the chunk.
+    pub depth: usize,
+    /// The `generation` of the signatures it was compiled for.
+    pub generation: u64,
+}
+
+/// What `key` calls now.
+pub(super) fn callee(ctx: &Context, key: &str) -> Option<Callee> {
+    match ctx.primitives.get(key) {
+        Some(handler) => Some(Callee::Primitive(handler.clone())),
+        None => ctx.procs.get(key).cloned().map(Callee::Proc),
+    }
+}
+
+impl Chunk {
+    fn new(ctx: &Context) -> Chunk {
+        Chunk {
+            ops: vec![],
+            spans: vec![],
+            consts: vec![],
+            names: vec![],
+            calls: vec![],
+            resumes: vec![],
+            depth: 0,
+            generation: ctx.generation,
+        }
+    }
+
+    fn emit(&mut self, op: Op, span: Span) {
+        self.ops.push(op);
+        self.spans.push(span);
+    }
+
+    fn name(&mut self, text: &str) -> u32 {
+        self.names.push(Name {
+            text: text.to_string(),
+            key: fold_case(text),
+        });
+        (self.names.len() - 1) as u32
+    }
+
+    fn call(&mut self, ctx: &Context, text: &str) -> u32 {
+        self.calls.push(Call {
+            text: text.to_string(),
+            callee: callee(ctx, &fold_case(text)),
+        });
+        (self.calls.len() - 1) as u32
+    }
+
+    fn resume(&mut self, code: &Arc<Code>, i: usize, at: usize) -> u32 {
+        self.resumes.push(Resume {
+            code: code.clone(),
+            i,
+            at,
+            end: 0,
+        });
+        (self.resumes.len() - 1) as u32
+    }
+
+    /// Emits code running the statements of `code`, as far as they parse,
+    /// with `height` values on the stack.
+    fn code(&mut self, ctx: &Context, code: &Arc<Code>, height: usize) {
+        let mut resumes = vec![];
+        let mut at = 0;
+        for i in 0.. {
+            let span = code.tokens().get(at).map(|t| t.span).unwrap_or_default();
+            let stat = statement(ctx, code, i, at);
+            if let Ok(None) = stat {
+                break;
+            }
+            let k = self.resume(code, i, at);
+            resumes.push(k);
+            if let Ok(Some(st)) = &stat {
+                if let Stat::Exp(e) = &st.stat {
+                    self.emit(Op::Check(k), span);
+                    self.exp(ctx, e, height);
+                    self.emit(Op::Pop, span);
+                    at = st.next;
+                    continue;
+                }
+            }
+            self.emit(Op::Rest(k), span);
+            break;
+        }
+        let end = self.ops.len();
+        for k in resumes {
+            self.resumes[k as usize].end = end;
+        }
+    }
+
+    /// Emits an inlined call of `repeat` or `if` with its list, if `pr` is
+    /// one of them.
+    fn control(
+        &mut self,
+        ctx: &Context,
+        pr: &str,
+        args: &[Exp],
+        span: Span,
+        height: usize,
+    ) -> bool {
+        let [first, Exp::Const(Value::List(list))] = args else {
+            return false;
+        };
+        let key = fold_case(pr);
+        let builtin = matches!(ctx.primitives.get(&key), Some(Handler::Builtin(_)));
+        if !builtin || key != "repeat" && key != "if" {
+            return false;
+        }
+        self.exp(ctx, first, height);
+        let call = self.call(ctx, pr);
+        let jump = self.ops.len();
+        self.emit(Op::Rest(0), span);
+        self.code(ctx, list, height);
+        if key == "repeat" {
+            let k = self.resume(list, 0, 0);
+            self.emit(Op::Next(k), span);
+        }
+        let end = self.ops.len() as u32;
+        self.ops[jump] = match &key[..] {
+            "repeat" => Op::Repeat(call, end),
+            _ => Op::If(call, end),
+        };
+        self.exp(ctx, &Exp::Const(Value::Void), height);
+        true
+    }
+
+    /// Emits code leaving the value of `exp` on the stack, `height` is the
+    /// number of values already there.
+    fn exp(&mut self, ctx: &Context, exp: &Exp, height: usize) {
+        self.depth = self.depth.max(height + 1);
+        match exp {
+            Exp::Const(v) => {
+                self.consts.push(v.clone());
+                let i = (self.consts.len() - 1) as u32;
+                self.emit(Op::Const(i), Span::default());
+            }
+            Exp::Var(s, span) => {
+                let i = self.name(s);
+                self.emit(Op::Load(i), *span);
+            }
+            Exp::Oper(op, e1, e2, span) => {
+                self.exp(ctx, e1, height);
+                self.exp(ctx, e2, height + 1);
+                let op = match op {
+                    OP::Sub => Op::Sub,
+                    OP::Mul => Op::Mul,
+                    OP::Div => Op::Div,
+                    OP::Le => Op::Le,
+                };
+                self.emit(op, *span);
+            }
+            Exp::Call(pr, args, span) => {
+                if self.control(ctx, pr, args, *span, height) {
+                    return;
+                }
+                for (i, e) in args.iter().enumerate() {
+                    self.exp(ctx, e, height + i);
+                }
+                let i = self.call(ctx, pr);
+                self.emit(Op::Call(i, args.len() as u32), *span);
+            }
+        }
+    }
+}
+
+/// Compiles `exp`, its calls going to the procedures `ctx` has now.
+pub fn compile(exp: &Exp, ctx: &Context) -> Chunk {
+    let mut chunk = Chunk::new(ctx);
+    chunk.exp(ctx, exp, 0);
+    chunk
+}
+
+/// Compiles the statements of `code`, up to the first which is a definition
+/// or doesn't parse, which is left for the tree walker to run when it is
+/// reached.
+pub fn compile_code(code: &Arc<Code>, ctx: &Context) -> Chunk {
+    let mut chunk = Chunk::new(ctx);
+    chunk.code(ctx, code, 0);
+    chunk
+}
@@ -0,0 +1,133 @@
+use crate::interpretator::value::Value;
+use crate::interpretator::Context;
+use crate::parser::fold_case;
+use crate::parser::parse_statement;
+use crate::parser::Exp;
+use crate::parser::Stat;
+use crate::parser::OP;
+use crate::tokenizer::Span;
+use crate::unsee::Unsee;
+
+/// Simplifies a freshly parsed statement: operations on constants are
+/// computed, `if` with a false constant condition is dropped and calls of
+/// procedures which only output an expression of their inputs are replaced
+/// by that expression.
+///
+/// The result behaves like the original, except that errors in an inlined
+/// body are reported where the body is, without its call frame.
+pub fn stat(ctx: &Context, stat: Stat) -> Stat {
+    match stat {
+        Stat::Exp(e) => Stat::Exp(exp(ctx, e)),
+        def => def,
+    }
+}
+
+fn exp(ctx: &Context, e: Exp) -> Exp {
+    match e {
+        Exp::Oper(op, x, y, span) => fold(op, exp(ctx, *x), exp(ctx, *y), span),
+        Exp::Call(pr, args, span) => {
+            let args: Vec<Exp> = args.into_iter().map(|a| exp(ctx, a)).collect();
+            let key = fold_case(&pr);
+            match args.first() {
+                Some(Exp::Const(v)) if key == "if" && v.as_num() == Some(0.) => {
+                    return Exp::Const(Value::Void)
+                }
+                _ => (),
+            }
+            inline(ctx, &key, &args).unwrap_or(Exp::Call(pr, args, span))
+        }
+        e => e,
+    }
+}
+
+fn fold(op: OP, x: Exp, y: Exp, span: Span) -> Exp {
+    if let (Exp::Const(a), Exp::Const(b)) = (&x, &y) {
+        let (a, b) = (a.clone(), b.clone());
+        let v = match op {
+            OP::Sub => a - b,
+            OP::Mul => a * b,
+            OP::Div => a / b,
+            OP::Le => a.less(b),
+        };
+        // Errors are left to be reported when the statement runs.
+        if let Ok(v) = v {
+            return Exp::Const(v);
+        }
+    }
+    Exp::Oper(op, Box::new(x), Box::new(y), span)
+}
+
+/// Whether evaluating `e` calls no procedure.
+fn pure(e: &Exp) -> bool {
+    match e {
+        Exp::Call(..) => false,
+        Exp::Oper(_, x, y, _) => pure(x) && pure(y),
+        Exp::Const(_) | Exp::Var(..) => true,
+    }
+}
+
+/// Whether `e` has an operation on constants which could not be folded.
+fn fails(e: &Exp) -> bool {
+    match e {
+        Exp::Oper(_, x, y, _) => {
+            matches!((&**x, &**y), (Exp::Const(_), Exp::Const(_))) || fails(x) || fails(y)
+        }
+        _ => false,
+    }
+}
+
+fn uses(e: &Exp, name: &str) -> usize {
+    match e {
+        Exp::Oper(_, x, y, _) => uses(x, name) + uses(y, name),
+        Exp::Var(s, _) => (fold_case(s) == name) as usize,
+        _ => 0,
+    }
+}
+
+fn substitute(e: Exp, argv: &[String], args: &[Exp]) -> Exp {
+    match e {
+        Exp::Oper(op, x, y, span) => Exp::Oper(
+            op,
+            Box::new(substitute(*x, argv, args)),
+            Box::new(substitute(*y, argv, args)),
+            span,
+        ),
+        Exp::Var(s, span) => match argv.iter().position(|v| *v == fold_case(&s)) {
+            Some(i) => args[i].clone(),
+            None => Exp::Var(s, span),
+        },
+        e => e,
+    }
+}
+
+/// The body of the user procedure `key` with `args` put in for its inputs,
+/// if all it does is `output` a pure expression and putting them in neither
+/// skips nor repeats a computation.
+fn inline(ctx: &Context, key: &str, args: &[Exp]) -> Option<Exp> {
+    if ctx.is_primitive(key) {
+        return None;
+    }
+    let proc = ctx.procs.get(key)?;
+    let tokens = proc.get_body().tokens();
+    let mut iter = Unsee::wrap(tokens.iter());
+    let Ok(Some(Stat::Exp(Exp::Call(pr, mut out, _)))) = parse_statement(&ctx.signs, &mut iter)
+    else {
+        return None;
+    };
+    let body = out.pop()?;
+    if fold_case(&pr) != "output" || iter.consumed() != tokens.len() || !pure(&body) {
+        return None;
+    }
+    let argv: Vec<String> = proc.get_argv().into_iter().map(fold_case).collect();
+    let simple = argv.iter().zip(args).all(|(name, arg)| match arg {
+        Exp::Const(_) => true,
+        Exp::Var(..) => uses(&body, name) > 0,
+        arg => uses(&body, name) == 1 && pure(arg),
+    });
+    if !simple {
+        return None;
+    }
+    let e = exp(ctx, substitute(body, &argv, args));
+    // Keep the call frame in the message of an error sure to happen.
+    (!fails(&e)).then_some(e)
+}
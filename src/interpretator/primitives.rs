@@ -0,0 +1,603 @@
+use crate::error::LogoError;
+use crate::interpretator::interprete_run;
+use crate::interpretator::io_error;
+use crate::interpretator::list;
+use crate::interpretator::number;
+use crate::interpretator::read_line;
+use crate::interpretator::value::Value;
+use crate::interpretator::Context;
+use crate::interpretator::ExpResult;
+use crate::interpretator::Res;
+use crate::parser::fold_case;
+use crate::robot;
+use crate::robot::canvas::LineCap;
+use crate::robot::canvas::LineJoin;
+use crate::robot::canvas::PenMode;
+use crate::robot::canvas::Point;
+use crate::robot::SaveOptions;
+use crate::tokenizer::read_number;
+use crate::tokenizer::tokenize;
+use crate::tokenizer::Token;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::io::Write;
+use std::sync::Arc;
+
+/// A procedure written in Rust. Embedders add their own with
+/// `Interpreter::register`. It is shared by the threads an interpreter
+/// moves to, hence `Send + Sync`.
+pub trait Primitive: Send + Sync {
+    /// The name it is called by, then its other spellings.
+    fn names(&self) -> &[&str];
+
+    /// Number of inputs it takes.
+    fn arity(&self) -> usize;
+
+    /// Whether it outputs a value. Commands, which output `Value::Void`,
+    /// don't.
+    fn outputs(&self) -> bool {
+        false
+    }
+
+    /// Runs it with its inputs. Commands output `Value::Void`.
+    fn call(&self, ctx: &mut Context, inputs: Vec<Value>) -> Result<Value, LogoError>;
+}
+
+type Run = fn(&mut Context, &str, Vec<Value>) -> Res;
+
+/// A primitive of the language itself. Unlike a `Primitive` it may end the
+/// procedure it is called from.
+struct Builtin {
+    names: &'static [&'static str],
+    arity: usize,
+    /// Whether it outputs a value, which `check` wants used.
+    outputs: bool,
+    run: Run,
+}
+
+/// How a primitive is run.
+#[derive(Clone)]
+pub(super) enum Handler {
+    Builtin(Run),
+    Native(Arc<dyn Primitive>),
+}
+
+const BUILTINS: [Builtin; 45] = [
+    Builtin {
+        names: &["stop"],
+        arity: 0,
+        outputs: false,
+        run: |_, _, _| Ok(ExpResult::Exit(Value::Void)),
+    },
+    Builtin {
+        names: &["output"],
+        arity: 1,
+        outputs: false,
+        run: |_, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            Ok(ExpResult::Exit(v))
+        },
+    },
+    Builtin {
+        names: &["run"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [code] = inputs(pr, vals)?;
+            interprete_run(ctx, pr, code)
+        },
+    },
+    Builtin {
+        names: &["repeat"],
+        arity: 2,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [num, code] = inputs(pr, vals)?;
+            for i in 0..number(pr, num)? as i32 {
+                ctx.vars
+                    .insert("repcount".to_string(), Value::Num(i as f32));
+                if let ExpResult::Exit(res) = interprete_run(ctx, pr, code.clone())? {
+                    return Ok(ExpResult::Exit(res));
+                }
+            }
+            done()
+        },
+    },
+    Builtin {
+        names: &["if"],
+        arity: 2,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [que, code] = inputs(pr, vals)?;
+            match number(pr, que)? != 0. {
+                true => interprete_run(ctx, pr, code),
+                false => done(),
+            }
+        },
+    },
+    Builtin {
+        names: &["make"],
+        arity: 2,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [name, v] = inputs(pr, vals)?;
+            ctx.vars.insert(fold_case(&name.to_string()), v);
+            done()
+        },
+    },
+    Builtin {
+        names: &["forward", "fd"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [d] = inputs(pr, vals)?;
+            ctx.robot.forward(number(pr, d)?);
+            done()
+        },
+    },
+    Builtin {
+        names: &["back", "bk"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [d] = inputs(pr, vals)?;
+            ctx.robot.back(number(pr, d)?);
+            done()
+        },
+    },
+    Builtin {
+        names: &["right", "rt"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [d] = inputs(pr, vals)?;
+            ctx.robot.right(number(pr, d)? * PI / 180.0);
+            done()
+        },
+    },
+    Builtin {
+        names: &["left", "lt"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [d] = inputs(pr, vals)?;
+            ctx.robot.left(number(pr, d)? * PI / 180.0);
+            done()
+        },
+    },
+    Builtin {
+        names: &["setpencolor", "setcolor"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [color] = inputs(pr, vals)?;
+            ctx.robot.setpencolor(color.to_string());
+            done()
+        },
+    },
+    Builtin {
+        names: &["setpensize"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            let width = pensize(&v).ok_or_else(|| LogoError::doesnt_like(pr, v))?;
+            ctx.robot.setpensize(width);
+            done()
+        },
+    },
+    Builtin {
+        names: &["pensize"],
+        arity: 0,
+        outputs: true,
+        run: |ctx, _, _| {
+            let width = ctx.robot.pensize();
+            value(Value::from(tokenize(&format!("{width} {width}"))?))
+        },
+    },
+    Builtin {
+        names: &["setlinecap"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            let cap = LineCap::from_name(&fold_case(&v.to_string()))
+                .ok_or_else(|| LogoError::doesnt_like(pr, v))?;
+            ctx.robot.setlinecap(cap);
+            done()
+        },
+    },
+    Builtin {
+        names: &["setlinejoin"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            let join = LineJoin::from_name(&fold_case(&v.to_string()))
+                .ok_or_else(|| LogoError::doesnt_like(pr, v))?;
+            ctx.robot.setlinejoin(join);
+            done()
+        },
+    },
+    Builtin {
+        names: &["home"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.home();
+            done()
+        },
+    },
+    Builtin {
+        names: &["label"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [text] = inputs(pr, vals)?;
+            ctx.robot.label(text.to_string());
+            done()
+        },
+    },
+    Builtin {
+        names: &["setlabelheight"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [h] = inputs(pr, vals)?;
+            ctx.robot.setlabelheight(number(pr, h)?);
+            done()
+        },
+    },
+    Builtin {
+        names: &["penup", "pu"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.penup();
+            done()
+        },
+    },
+    Builtin {
+        names: &["pendown", "pd"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.pendown();
+            done()
+        },
+    },
+    Builtin {
+        names: &["penpaint", "ppt"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.setpenmode(PenMode::Paint);
+            done()
+        },
+    },
+    Builtin {
+        names: &["penerase", "pe"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.setpenmode(PenMode::Erase);
+            done()
+        },
+    },
+    Builtin {
+        names: &["penreverse", "px"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.setpenmode(PenMode::Reverse);
+            done()
+        },
+    },
+    Builtin {
+        names: &["penmode"],
+        arity: 0,
+        outputs: true,
+        run: |ctx, _, _| value(Value::Str(ctx.robot.penmode().name().to_string())),
+    },
+    Builtin {
+        names: &["clean"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.clean();
+            done()
+        },
+    },
+    Builtin {
+        names: &["clearscreen", "cs"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.clearscreen();
+            done()
+        },
+    },
+    Builtin {
+        names: &["wait"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [n] = inputs(pr, vals)?;
+            // In sixtieths of a second.
+            ctx.robot.wait(number(pr, n)? / 60.);
+            done()
+        },
+    },
+    Builtin {
+        names: &["window"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            writeln!(ctx.stdout, "window").map_err(io_error)?;
+            done()
+        },
+    },
+    Builtin {
+        names: &["hideturtle", "ht"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.hideturtle();
+            done()
+        },
+    },
+    Builtin {
+        names: &["showturtle", "st"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.showturtle();
+            done()
+        },
+    },
+    Builtin {
+        names: &["shownp", "shown?"],
+        arity: 0,
+        outputs: true,
+        run: |ctx, _, _| value(Value::from(ctx.robot.shown())),
+    },
+    Builtin {
+        names: &["filled"],
+        arity: 2,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [color, code] = inputs(pr, vals)?;
+            ctx.robot.begin_fill();
+            let res = interprete_run(ctx, pr, code);
+            ctx.robot.end_fill(color.to_string());
+            res
+        },
+    },
+    Builtin {
+        names: &["fill"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.fill();
+            done()
+        },
+    },
+    Builtin {
+        names: &["setfloodcolor"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [color] = inputs(pr, vals)?;
+            ctx.robot.setfloodcolor(color.to_string());
+            done()
+        },
+    },
+    Builtin {
+        names: &["floodcolor"],
+        arity: 0,
+        outputs: true,
+        run: |ctx, _, _| value(Value::Str(ctx.robot.floodcolor().to_string())),
+    },
+    Builtin {
+        names: &["setshape"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            let shape = shape(&v).ok_or_else(|| LogoError::doesnt_like(pr, v))?;
+            ctx.robot.setshape(shape);
+            done()
+        },
+    },
+    Builtin {
+        names: &["stamp"],
+        arity: 0,
+        outputs: false,
+        run: |ctx, _, _| {
+            ctx.robot.stamp();
+            done()
+        },
+    },
+    Builtin {
+        names: &["print", "pr"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            writeln!(ctx.stdout, "{v}").map_err(io_error)?;
+            done()
+        },
+    },
+    Builtin {
+        names: &["minus"],
+        arity: 1,
+        outputs: true,
+        run: |_, pr, vals| {
+            let [n] = inputs(pr, vals)?;
+            value(Value::Num(-number(pr, n)?))
+        },
+    },
+    Builtin {
+        names: &["pick"],
+        arity: 1,
+        outputs: true,
+        run: |_, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            let vs = list(pr, v.clone())?;
+            let choice = vs
+                .words()
+                .choose(&mut rand::thread_rng())
+                .cloned()
+                .ok_or_else(|| LogoError::doesnt_like(pr, v.clone()))?;
+            value(Value::Str(choice))
+        },
+    },
+    Builtin {
+        names: &["random"],
+        arity: 1,
+        outputs: true,
+        run: |_, pr, vals| {
+            let [v] = inputs(pr, vals)?;
+            let n: i32 = number(pr, v.clone())? as i32;
+            if n <= 0 {
+                return Err(LogoError::doesnt_like(pr, v));
+            }
+            value(Value::Num(rand::thread_rng().gen_range(0..n) as f32))
+        },
+    },
+    Builtin {
+        names: &["sentence"],
+        arity: 2,
+        outputs: true,
+        run: |_, pr, vals| {
+            let [l1, l2] = inputs(pr, vals)?;
+            let mut l: Vec<Token> = list(pr, l1)?.tokens().to_vec();
+            l.extend_from_slice(list(pr, l2)?.tokens());
+            value(Value::from(l))
+        },
+    },
+    Builtin {
+        names: &["savepict"],
+        arity: 1,
+        outputs: false,
+        run: |ctx, pr, vals| {
+            let [file] = inputs(pr, vals)?;
+            ctx.save(&file.to_string(), SaveOptions::default())?;
+            done()
+        },
+    },
+    Builtin {
+        names: &["readword"],
+        arity: 0,
+        outputs: true,
+        run: |ctx, _, _| value(read_line(ctx)?.map_or(Value::from(vec![]), Value::Str)),
+    },
+    Builtin {
+        names: &["readlist"],
+        arity: 0,
+        outputs: true,
+        run: |ctx, _, _| {
+            let line = read_line(ctx)?.unwrap_or_default();
+            value(Value::from(tokenize(&line)?))
+        },
+    },
+];
+
+/// The inputs of the primitive `pr`, which the parser gives as many as it
+/// takes unless it was told another arity.
+fn inputs<const N: usize>(pr: &str, vals: Vec<Value>) -> Result<[Value; N], LogoError> {
+    let n = vals.len();
+    vals.try_into().map_err(|_| match n < N {
+        true => LogoError::NotEnoughInputs(pr.to_string()),
+        false => LogoError::TooManyInputs(pr.to_string()),
+    })
+}
+
+fn done() -> Res {
+    Ok(ExpResult::Outcome(Value::Void))
+}
+
+/// A shape named as `robot::shape` knows it, or given as the coordinates
+/// `[x1 y1 x2 y2 ...]` of at least two corners.
+fn shape(v: &Value) -> Option<Vec<Point>> {
+    let tokens = match v {
+        Value::List(l) => l.tokens(),
+        v => return robot::shape(&fold_case(&v.to_string())),
+    };
+    let xy: Vec<f32> = tokens
+        .iter()
+        .map(|t| read_number(&t.text))
+        .collect::<Option<_>>()?;
+    if xy.len() < 4 || !xy.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(xy.chunks(2).map(|c| Point { x: c[0], y: c[1] }).collect())
+}
+
+/// The width of a pen given as a number or, as in UCBLogo, as a list of
+/// its width and height, which it must equal.
+fn pensize(v: &Value) -> Option<f32> {
+    let width = match v {
+        Value::List(l) => match l.tokens() {
+            [w, h] => {
+                let w = read_number(&w.text)?;
+                (read_number(&h.text) == Some(w)).then_some(w)?
+            }
+            _ => return None,
+        },
+        v => v.as_num()?,
+    };
+    (width >= 0.).then_some(width)
+}
+
+fn value(v: Value) -> Res {
+    Ok(ExpResult::Outcome(v))
+}
+
+impl Handler {
+    pub(super) fn call(&self, ctx: &mut Context, pr: &str, vals: Vec<Value>) -> Res {
+        match self {
+            Handler::Builtin(run) => run(ctx, pr, vals),
+            Handler::Native(p) => p.call(ctx, vals).map(ExpResult::Outcome),
+        }
+    }
+}
+
+/// Every name of the primitives of the language and its arity.
+pub fn signatures() -> impl Iterator<Item = (&'static str, usize)> {
+    BUILTINS
+        .iter()
+        .flat_map(|b| b.names.iter().map(|&name| (name, b.arity)))
+}
+
+/// Whether `key` names a primitive of the language, which user procedures
+/// can't replace.
+pub fn is_primitive(key: &str) -> bool {
+    BUILTINS.iter().any(|b| b.names.contains(&key))
+}
+
+/// Whether `key` names a primitive of the language which outputs a value.
+pub fn outputs(key: &str) -> bool {
+    BUILTINS.iter().any(|b| b.outputs && b.names.contains(&key))
+}
+
+/// The usual spelling of the primitive `key`.
+pub fn canonical(key: &str) -> Option<&'static str> {
+    BUILTINS
+        .iter()
+        .find(|b| b.names.contains(&key))
+        .map(|b| b.names[0])
+}
+
+pub(super) fn builtins() -> HashMap<String, Handler> {
+    BUILTINS
+        .iter()
+        .flat_map(|b| {
+            b.names
+                .iter()
+                .map(|&name| (name.to_string(), Handler::Builtin(b.run)))
+        })
+        .collect()
+}
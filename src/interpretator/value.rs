@@ -1,44 +1,81 @@
+use crate::error::LogoError;
+use crate::parser::code::Code;
+use crate::tokenizer::read_number;
+use crate::tokenizer::Token;
 use core::ops::{Div, Mul, Sub};
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::sync::Arc;
 use Value::*;
 
 #[derive(PartialEq, PartialOrd, Debug, Clone)]
 pub enum Value {
     Str(String),
     Num(f32),
-    List(Vec<String>),
+    List(Arc<Code>),
     Void,
 }
 
-impl Sub for Value {
-    type Output = Value;
-    fn sub(self, rhs: Value) -> Value {
-        match (self, rhs) {
-            (Num(a), Num(b)) => Num(a - b),
-            _ => panic!(),
+impl Value {
+    /// Numeric value of a number or of a word which reads as one.
+    pub fn as_num(&self) -> Option<f32> {
+        match self {
+            Num(n) => Some(*n),
+            Str(s) => read_number(s),
+            _ => None,
+        }
+    }
+
+    /// How the value is quoted in error messages: words get their `"`.
+    pub fn show(&self) -> String {
+        match self {
+            Str(s) => format!("\"{s}"),
+            Void => "nothing".to_string(),
+            v => v.to_string(),
         }
     }
+
+    pub fn less(self, rhs: Value) -> Result<Value, LogoError> {
+        let (a, b) = operands("<", self, rhs)?;
+        Ok(Value::from(a < b))
+    }
+}
+
+fn operands(who: &str, lhs: Value, rhs: Value) -> Result<(f32, f32), LogoError> {
+    let a = lhs
+        .as_num()
+        .ok_or_else(|| LogoError::doesnt_like(who, lhs.clone()))?;
+    let b = rhs
+        .as_num()
+        .ok_or_else(|| LogoError::doesnt_like(who, rhs.clone()))?;
+    Ok((a, b))
+}
+
+impl Sub for Value {
+    type Output = Result<Value, LogoError>;
+    fn sub(self, rhs: Value) -> Result<Value, LogoError> {
+        let (a, b) = operands("-", self, rhs)?;
+        Ok(Num(a - b))
+    }
 }
 
 impl Mul for Value {
-    type Output = Value;
-    fn mul(self, rhs: Value) -> Value {
-        match (self, rhs) {
-            (Num(a), Num(b)) => Num(a * b),
-            _ => panic!(),
-        }
+    type Output = Result<Value, LogoError>;
+    fn mul(self, rhs: Value) -> Result<Value, LogoError> {
+        let (a, b) = operands("*", self, rhs)?;
+        Ok(Num(a * b))
     }
 }
 
 impl Div for Value {
-    type Output = Value;
-    fn div(self, rhs: Value) -> Value {
-        match (self, rhs) {
-            (Num(a), Num(b)) => Num(a / b),
-            _ => panic!(),
+    type Output = Result<Value, LogoError>;
+    fn div(self, rhs: Value) -> Result<Value, LogoError> {
+        let (a, b) = operands("/", self, rhs)?;
+        if b == 0. {
+            return Err(LogoError::doesnt_like("/", Num(b)));
         }
+        Ok(Num(a / b))
     }
 }
 
@@ -51,14 +88,8 @@ impl From<f32> for Value {
 impl TryFrom<Value> for f32 {
     type Error = String;
     fn try_from(v: Value) -> Result<f32, String> {
-        match v {
-            Str(s) => match s.parse() {
-                Ok(n) => Ok(n),
-                Err(_) => Err("Expected number".to_string()),
-            },
-            Num(n) => Ok(n),
-            _ => Err("Type error: It is not a number!".to_string()),
-        }
+        v.as_num()
+            .ok_or_else(|| format!("{} is not a number", v.show()))
     }
 }
 
@@ -75,9 +106,9 @@ impl TryFrom<Value> for bool {
     }
 }
 
-impl From<Vec<String>> for Value {
-    fn from(value: Vec<String>) -> Value {
-        List(value)
+impl From<Vec<Token>> for Value {
+    fn from(value: Vec<Token>) -> Value {
+        List(Arc::new(Code::new(value)))
     }
 }
 
@@ -85,9 +116,9 @@ impl TryFrom<Value> for Vec<String> {
     type Error = String;
     fn try_from(v: Value) -> Result<Self, String> {
         if let List(l) = v {
-            Ok(l)
+            Ok(l.words())
         } else {
-            Err("Type error: It is not a list!".to_string())
+            Err(format!("{} is not a list", v.show()))
         }
     }
 }
@@ -97,7 +128,7 @@ impl Display for Value {
         match self {
             Str(s) => formatter.write_fmt(format_args!("{s}")),
             Num(n) => formatter.write_fmt(format_args!("{n}")),
-            List(l) => formatter.write_fmt(format_args!("[ {} ]", l.join(" "))),
+            List(l) => formatter.write_fmt(format_args!("[ {} ]", l.words().join(" "))),
             Void => formatter.write_fmt(format_args!("Void")),
         }
     }
@@ -0,0 +1,252 @@
+use crate::error::LogoError;
+use crate::interpretator::bind_inputs;
+use crate::interpretator::bytecode::callee;
+use crate::interpretator::bytecode::Callee;
+use crate::interpretator::bytecode::Chunk;
+use crate::interpretator::bytecode::Op;
+use crate::interpretator::interete;
+use crate::interpretator::number;
+use crate::interpretator::run_rest;
+use crate::interpretator::value::Value;
+use crate::interpretator::Context;
+use crate::interpretator::ExpResult;
+use crate::interpretator::Hidden;
+use crate::interpretator::Res;
+use crate::parser::fold_case;
+use crate::parser::Procedure;
+use crate::tokenizer::Span;
+use std::sync::Arc;
+
+/// An inlined `repeat` being run.
+struct Loop {
+    count: i32,
+    n: i32,
+    /// Where its list starts.
+    start: usize,
+}
+
+/// A chunk being run, gone back to once the procedure it calls is done.
+struct Frame {
+    chunk: Arc<Chunk>,
+    pc: usize,
+    /// Where its values and loops start.
+    stack: usize,
+    loops: usize,
+    /// The procedure it runs, with the values its inputs hid. `None` for
+    /// the chunk given to `exec`.
+    proc: Option<(Arc<Procedure>, Hidden)>,
+}
+
+struct Vm {
+    stack: Vec<Value>,
+    loops: Vec<Loop>,
+    frames: Vec<Frame>,
+}
+
+/// Runs a chunk on a value stack. The user procedures it calls run on the
+/// same stack, each in a frame of its own.
+pub(super) fn exec(ctx: &mut Context, chunk: &Arc<Chunk>) -> Res {
+    let mut vm = Vm {
+        stack: Vec::with_capacity(chunk.depth),
+        loops: vec![],
+        frames: vec![Frame {
+            chunk: chunk.clone(),
+            pc: 0,
+            stack: 0,
+            loops: 0,
+            proc: None,
+        }],
+    };
+    let mut e = match vm.run(ctx) {
+        Err(e) => e,
+        res => return res,
+    };
+    // The error escapes from the procedures still running.
+    while let Some(Frame {
+        proc: Some((proc, save)),
+        ..
+    }) = vm.frames.pop()
+    {
+        ctx.restore_values_from_context(save);
+        let caller = vm.frames.last().unwrap();
+        let span = caller.chunk.spans[caller.pc - 1];
+        e = e.called_from(proc.get_name()).at(span.start);
+    }
+    Err(e)
+}
+
+fn repcount(ctx: &mut Context, count: i32) {
+    match ctx.vars.get_mut("repcount") {
+        Some(v) => *v = Value::Num(count as f32),
+        None => {
+            ctx.vars
+                .insert("repcount".to_string(), Value::Num(count as f32));
+        }
+    }
+}
+
+impl Vm {
+    fn run(&mut self, ctx: &mut Context) -> Res {
+        let mut chunk = self.frames[0].chunk.clone();
+        let mut pc = 0;
+        loop {
+            let left = match chunk.ops.get(pc) {
+                Some(&op) => {
+                    let span = chunk.spans[pc];
+                    pc += 1;
+                    self.op(ctx, &mut chunk, &mut pc, op, span)?
+                }
+                None => {
+                    let base = self.frames.last().unwrap().stack;
+                    let v = match self.stack.len() > base {
+                        true => self.stack.pop().unwrap(),
+                        false => Value::Void,
+                    };
+                    Some(ExpResult::Outcome(v))
+                }
+            };
+            let Some(res) = left else {
+                continue;
+            };
+            if self.frames.len() == 1 {
+                return Ok(res);
+            }
+            // The procedure is done, with what it output or stopped with.
+            let (ExpResult::Outcome(v) | ExpResult::Exit(v)) = res;
+            let frame = self.frames.pop().unwrap();
+            if let Some((_, save)) = frame.proc {
+                ctx.restore_values_from_context(save);
+            }
+            self.stack.truncate(frame.stack);
+            self.loops.truncate(frame.loops);
+            self.stack.push(v);
+            let caller = self.frames.last().unwrap();
+            (chunk, pc) = (caller.chunk.clone(), caller.pc);
+        }
+    }
+
+    /// Runs `op`, which `chunk` has before `pc`. Outputs how the chunk was
+    /// left, if it was.
+    fn op(
+        &mut self,
+        ctx: &mut Context,
+        chunk: &mut Arc<Chunk>,
+        pc: &mut usize,
+        op: Op,
+        span: Span,
+    ) -> Result<Option<ExpResult>, LogoError> {
+        let at = |e: LogoError| e.at(span.start);
+        match op {
+            Op::Const(i) => self.stack.push(chunk.consts[i as usize].clone()),
+            Op::Load(i) => {
+                let name = &chunk.names[i as usize];
+                match ctx.vars.get(&name.key) {
+                    Some(v) => self.stack.push(v.clone()),
+                    None => return Err(at(LogoError::NoValue(name.text.clone()))),
+                }
+            }
+            Op::Sub | Op::Mul | Op::Div | Op::Le => {
+                let y = self.stack.pop().unwrap();
+                let x = self.stack.pop().unwrap();
+                let v = match op {
+                    Op::Sub => x - y,
+                    Op::Mul => x * y,
+                    Op::Div => x / y,
+                    _ => x.less(y),
+                };
+                self.stack.push(v.map_err(at)?);
+            }
+            Op::Call(i, argc) => {
+                let call = &chunk.calls[i as usize];
+                let args = self.stack.split_off(self.stack.len() - argc as usize);
+                // Once a procedure is defined the call may go elsewhere.
+                let callee = match ctx.generation == chunk.generation {
+                    true => call.callee.clone(),
+                    false => callee(ctx, &fold_case(&call.text)),
+                };
+                match callee {
+                    Some(Callee::Primitive(handler)) => {
+                        match handler.call(ctx, &call.text, args).map_err(at)? {
+                            ExpResult::Outcome(v) => self.stack.push(v),
+                            exit => return Ok(Some(exit)),
+                        }
+                    }
+                    Some(Callee::Proc(proc)) => {
+                        self.frames.last_mut().unwrap().pc = *pc;
+                        *chunk = self.enter(ctx, proc, args);
+                        *pc = 0;
+                    }
+                    None => return Err(at(LogoError::unknown(&call.text, ctx.signs.keys()))),
+                }
+            }
+            Op::Pop => match self.stack.pop().unwrap() {
+                Value::Void => (),
+                v => return Err(at(LogoError::UnusedValue(v))),
+            },
+            Op::Check(_) if ctx.generation == chunk.generation => (),
+            Op::Check(k) | Op::Rest(k) => {
+                let resume = &chunk.resumes[k as usize];
+                match run_rest(ctx, &resume.code, resume.i, resume.at)? {
+                    ExpResult::Outcome(_) => *pc = resume.end,
+                    exit => return Ok(Some(exit)),
+                }
+            }
+            Op::Repeat(i, end) => {
+                let text = &chunk.calls[i as usize].text;
+                let n = number(text, self.stack.pop().unwrap()).map_err(at)? as i32;
+                match n > 0 {
+                    true => {
+                        let start = *pc;
+                        self.loops.push(Loop { count: 0, n, start });
+                        repcount(ctx, 0);
+                    }
+                    false => *pc = end as usize,
+                }
+            }
+            Op::Next(k) => {
+                let lp = self.loops.last_mut().unwrap();
+                lp.count += 1;
+                if ctx.generation != chunk.generation {
+                    // The rest of the loop runs the list as it now parses.
+                    let (count, n) = (lp.count, lp.n);
+                    self.loops.pop();
+                    let code = &chunk.resumes[k as usize].code;
+                    for count in count..n {
+                        repcount(ctx, count);
+                        if let exit @ ExpResult::Exit(_) = interete(ctx, code)? {
+                            return Ok(Some(exit));
+                        }
+                    }
+                } else if lp.count < lp.n {
+                    let count = lp.count;
+                    *pc = lp.start;
+                    repcount(ctx, count);
+                } else {
+                    self.loops.pop();
+                }
+            }
+            Op::If(i, end) => {
+                let text = &chunk.calls[i as usize].text;
+                if number(text, self.stack.pop().unwrap()).map_err(at)? == 0. {
+                    *pc = end as usize;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Starts running the body of `proc` with its inputs, which outputs
+    /// to where it was called from once done.
+    fn enter(&mut self, ctx: &mut Context, proc: Arc<Procedure>, args: Vec<Value>) -> Arc<Chunk> {
+        let save = bind_inputs(ctx, &proc, args);
+        let chunk = proc.get_body().chunk(ctx);
+        self.frames.push(Frame {
+            chunk: chunk.clone(),
+            pc: 0,
+            stack: self.stack.len(),
+            loops: self.loops.len(),
+            proc: Some((proc, save)),
+        });
+        chunk
+    }
+}
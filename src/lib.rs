@@ -0,0 +1,180 @@
+//! A Logo interpreter whose turtle draws SVG.
+//!
+//! ```
+//! let mut logo = logo::Interpreter::new(logo::Config::default());
+//! logo.run("repeat 4 [ fd 100 rt 90 ]").unwrap();
+//! assert!(logo.render_svg().contains("<path"));
+//! ```
+
+use crate::robot::pdf::Pdf;
+use crate::tokenizer::tokenize;
+use crate::tokenizer::Kind;
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::Arc;
+
+mod check;
+pub mod error;
+mod format;
+mod interpretator;
+mod parser;
+mod robot;
+mod tokenizer;
+mod unsee;
+
+pub use crate::check::Diagnostic;
+pub use crate::error::LogoError;
+pub use crate::format::format;
+pub use crate::interpretator::primitives::Primitive;
+pub use crate::interpretator::value::Value;
+pub use crate::interpretator::Config;
+pub use crate::interpretator::Context;
+pub use crate::interpretator::Engine;
+pub use crate::robot::animation::AnimationOptions;
+pub use crate::robot::canvas::Canvas;
+pub use crate::robot::canvas::Event;
+pub use crate::robot::canvas::LineCap;
+pub use crate::robot::canvas::LineJoin;
+pub use crate::robot::canvas::LineStyle;
+pub use crate::robot::canvas::PenMode;
+pub use crate::robot::canvas::Point;
+pub use crate::robot::gif_canvas::GifOptions;
+pub use crate::robot::pdf::PdfOptions;
+pub use crate::robot::raster::PngOptions;
+pub use crate::robot::SaveOptions;
+pub use crate::tokenizer::Pos;
+
+/// An interpreter keeping its procedures, variables and drawing from one
+/// call to the next. It is `Send`, to run on another thread than the one
+/// which made it.
+pub struct Interpreter {
+    ctx: Context,
+}
+
+impl Interpreter {
+    pub fn new(config: Config) -> Interpreter {
+        Interpreter {
+            ctx: Context::new(config),
+        }
+    }
+
+    /// Where `print` and the like write, the standard output by default.
+    pub fn set_stdout(&mut self, stdout: impl Write + Send + 'static) {
+        self.ctx.stdout = Box::new(stdout);
+    }
+
+    /// Where `readword` and `readlist` read lines from, the standard input
+    /// by default.
+    pub fn set_stdin(&mut self, stdin: impl BufRead + Send + 'static) {
+        self.ctx.stdin = Box::new(stdin);
+    }
+
+    /// Adds a primitive written in Rust. It replaces any procedure or
+    /// primitive of the same name.
+    pub fn register(&mut self, primitive: impl Primitive + 'static) {
+        self.ctx.register(Arc::new(primitive));
+    }
+
+    /// Runs a program. A value left over by any of its statements is an
+    /// error.
+    pub fn run(&mut self, src: &str) -> Result<(), LogoError> {
+        interpretator::run(&mut self.ctx, tokenize(src)?)
+    }
+
+    /// Runs `src` and outputs the value of its last statement, as the REPL
+    /// shows it, or `Value::Void` if there is none.
+    pub fn eval(&mut self, src: &str) -> Result<Value, LogoError> {
+        interpretator::eval(&mut self.ctx, tokenize(src)?)
+    }
+
+    /// What the turtle has drawn so far.
+    pub fn drawing(&self) -> svg::Document {
+        self.ctx.plot()
+    }
+
+    pub fn render_svg(&self) -> String {
+        self.drawing().to_string()
+    }
+
+    /// An SVG document in which the drawing is traced the way the turtle
+    /// drew it.
+    pub fn render_animated_svg(&self, options: AnimationOptions) -> String {
+        self.ctx.animation(options).to_string()
+    }
+
+    /// What the turtle has drawn so far, as a PNG image.
+    pub fn render_png(&self, options: PngOptions) -> Result<Vec<u8>, LogoError> {
+        self.ctx.png(options)
+    }
+
+    /// What the turtle has drawn so far, as a PDF document of one page.
+    pub fn render_pdf(&self, options: PdfOptions) -> Vec<u8> {
+        self.ctx.pdf(options)
+    }
+
+    /// The drawing being drawn, as an animated GIF image.
+    pub fn render_gif(&self, options: GifOptions) -> Result<Vec<u8>, LogoError> {
+        self.ctx.gif(options)
+    }
+
+    /// Saves the drawing to `file`, as a PNG image if its name ends with
+    /// `.png`, as PDF if it ends with `.pdf`, as an animated GIF image if it
+    /// ends with `.gif` and as SVG otherwise.
+    pub fn save(&self, file: &str, options: SaveOptions) -> Result<(), LogoError> {
+        self.ctx.save(file, options)
+    }
+
+    /// What the turtle has drawn so far, as the calls it made to its canvas.
+    pub fn events(&self) -> &[Event] {
+        self.ctx.recording().events()
+    }
+
+    /// Draws what the turtle has drawn so far on another canvas, then the
+    /// turtle if it is shown.
+    pub fn replay(&self, canvas: &mut dyn Canvas) {
+        self.ctx.draw(canvas);
+    }
+
+    /// Names of the primitives and of the procedures defined so far.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.ctx.names().map(|n| &n[..])
+    }
+}
+
+/// A PDF document with a page for each of the drawings.
+pub fn render_pdf_pages(drawings: &[Interpreter], options: PdfOptions) -> Vec<u8> {
+    let mut pdf = Pdf::new(options);
+    for logo in drawings {
+        pdf.page(|page| logo.ctx.draw(page));
+    }
+    pdf.finish()
+}
+
+/// Finds problems in the program `src` without running it, ordered by
+/// position.
+pub fn check(src: &str) -> Result<Vec<Diagnostic>, LogoError> {
+    Ok(check::check(&tokenize(src)?))
+}
+
+/// Whether `src` needs more lines: a definition without its `end`, an
+/// unclosed bracket or a `~` at the end of the line.
+pub fn incomplete(src: &str) -> bool {
+    if src.trim_end().ends_with('~') {
+        return true;
+    }
+    let Ok(tokens) = tokenize(src) else {
+        return false;
+    };
+    let mut to = 0;
+    let mut depth = 0;
+    for tok in &tokens {
+        match &tok.text[..] {
+            "[" | "(" if tok.kind == Kind::Spec => depth += 1,
+            "]" | ")" if tok.kind == Kind::Spec => depth -= 1,
+            t if tok.kind == Kind::Name && t.eq_ignore_ascii_case("to") => to += 1,
+            t if tok.kind == Kind::Name && t.eq_ignore_ascii_case("end") => to -= 1,
+            _ => (),
+        }
+    }
+    to > 0 || depth > 0
+}
@@ -1,24 +1,202 @@
-use crate::interpretator::inter;
-use crate::tokenizer::lexer_rules;
+use logo::check;
+use logo::format;
+use logo::render_pdf_pages;
+use logo::AnimationOptions;
+use logo::Config;
+use logo::Engine;
+use logo::Interpreter;
+use logo::SaveOptions;
 use std::env;
 use std::fs;
+use std::process;
+use std::str::FromStr;
 
-mod interpretator;
-mod parser;
-mod robot;
-mod tokenizer;
-mod unsee;
+mod repl;
+
+const USAGE: &str = "usage: logo
+       logo [--vm] [-O] [--dump-tree] [-o OUT] [--animate] [--speed STEPS]
+            [--width PIXELS] [--dpi DPI]
+            [--every LINES] [--delay HUNDREDTHS] [--turtle]
+            [--page a3|a4|a5|letter|legal|WxH] [--margin MM] FILE
+       logo [OPTION]... -o OUT.pdf FILE...
+       logo check FILE
+       logo fmt [--check | --write] FILE...";
+
+fn read(file_path: &str) -> String {
+    fs::read_to_string(file_path).unwrap_or_else(|e| {
+        eprintln!("{file_path}: {e}");
+        process::exit(1);
+    })
+}
+
+/// `logo check`: reports problems found without running the program.
+fn lint(file_path: &str) {
+    let diags = check(&read(file_path)).unwrap_or_else(|e| {
+        eprintln!("{}", e.report(file_path));
+        process::exit(1);
+    });
+    for d in &diags {
+        println!("{file_path}:{d}");
+    }
+    if !diags.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    process::exit(2);
+}
+
+/// The number following an option.
+fn number<T: FromStr>(value: Option<String>) -> T {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| usage())
+}
+
+/// Width and height in millimetres of a page size, named or as `WxH`.
+fn page(size: Option<String>) -> (f32, f32) {
+    let size = size.unwrap_or_else(|| usage()).to_lowercase();
+    match &size[..] {
+        "a3" => (297., 420.),
+        "a4" => (210., 297.),
+        "a5" => (148., 210.),
+        "letter" => (215.9, 279.4),
+        "legal" => (215.9, 355.6),
+        size => {
+            let (w, h) = size.split_once('x').unwrap_or_else(|| usage());
+            (number(Some(w.to_string())), number(Some(h.to_string())))
+        }
+    }
+}
+
+/// Points in `mm` millimetres.
+fn points(mm: f32) -> f32 {
+    (mm as f64 * 72. / 25.4) as f32
+}
+
+/// `logo fmt`: prints the files laid out canonically, rewrites them with
+/// `--write` or with `--check` lists those which are not.
+fn reformat(args: &[String]) {
+    let (flag, files) = match args {
+        [flag, files @ ..] if flag.starts_with('-') => (Some(&flag[..]), files),
+        files => (None, files),
+    };
+    if files.is_empty() || flag.is_some_and(|f| f != "--check" && f != "--write") {
+        usage();
+    }
+    let mut failed = false;
+    for file_path in files {
+        let contents = read(file_path);
+        let formatted = match format(&contents) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}", e.report(file_path));
+                failed = true;
+                continue;
+            }
+        };
+        match flag {
+            Some("--check") if formatted != contents => {
+                println!("{file_path}");
+                failed = true;
+            }
+            Some("--check") => (),
+            Some(_) => fs::write(file_path, formatted).unwrap_or_else(|e| {
+                eprintln!("{file_path}: {e}");
+                process::exit(1);
+            }),
+            None => print!("{formatted}"),
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let file_path = args[1].clone();
-    println!("File: {file_path}");
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|a| &a[..]) {
+        None => {
+            return repl::repl().unwrap_or_else(|e| {
+                eprintln!("{e}");
+                process::exit(1);
+            })
+        }
+        Some("check") => {
+            let [_, file_path] = &args[..] else { usage() };
+            return lint(file_path);
+        }
+        Some("fmt") => return reformat(&args[1..]),
+        _ => (),
+    }
+    let mut config = Config::default();
+    let mut options = SaveOptions::default();
+    let mut out = "image.svg".to_string();
+    let mut files = vec![];
+    let mut args = args.drain(..);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--vm" => config.engine = Engine::Vm,
+            "-O" => config.optimize = true,
+            "--dump-tree" => config.dump_tree = true,
+            "-o" => out = args.next().unwrap_or_else(|| usage()),
+            "--animate" => {
+                options
+                    .animation
+                    .get_or_insert_with(AnimationOptions::default);
+            }
+            "--speed" => {
+                let animation = options
+                    .animation
+                    .get_or_insert_with(AnimationOptions::default);
+                let speed: f32 = number(args.next());
+                if !(speed.is_finite() && speed > 0.) {
+                    usage();
+                }
+                animation.speed = speed;
+            }
+            "--width" => {
+                let width = number(args.next());
+                (options.png.width, options.gif.width) = (width, width);
+            }
+            "--dpi" => options.png.dpi = number(args.next()),
+            "--every" => options.gif.every = number(args.next()),
+            "--delay" => options.gif.delay = number(args.next()),
+            "--turtle" => options.gif.turtle = true,
+            "--page" => {
+                let (w, h) = page(args.next());
+                (options.pdf.width, options.pdf.height) = (points(w), points(h));
+            }
+            "--margin" => options.pdf.margin = points(number(args.next())),
+            _ => files.push(arg),
+        }
+    }
+    // Only a PDF document has a page for each drawing.
+    if files.is_empty() || files.len() > 1 && !out.to_lowercase().ends_with(".pdf") {
+        usage();
+    }
+    let mut drawings = vec![];
+    for file_path in &files {
+        println!("File: {file_path}");
 
-    let contents = fs::read_to_string(file_path).expect("Should have been able to read the file");
-    let lexemes =
-        santiago::lexer::lex(&lexer_rules(), &contents).unwrap_or_else(|e| panic!("{}", e));
-    let data: Vec<&str> = lexemes.iter().map(|r| r.raw.as_ref()).collect();
-    let image = inter(data);
+        let contents = read(file_path);
+        let mut logo = Interpreter::new(config);
+        logo.run(&contents).unwrap_or_else(|e| {
+            eprintln!("{}", e.report(file_path));
+            process::exit(1);
+        });
+        drawings.push(logo);
+    }
 
-    svg::save("image.svg", &image).unwrap();
+    let saved = match &drawings[..] {
+        [logo] => logo.save(&out, options).map_err(|e| e.to_string()),
+        drawings => fs::write(&out, render_pdf_pages(drawings, options.pdf))
+            .map_err(|e| format!("{out}: {e}")),
+    };
+    saved.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
 }
@@ -1,8 +1,19 @@
+use crate::error::LogoError;
 use crate::interpretator::value::Value;
 use crate::parser::Exp::*;
 use crate::parser::OP::*;
+use crate::tokenizer::read_number;
+use crate::tokenizer::Kind;
+use crate::tokenizer::Span;
+use crate::tokenizer::Token;
 use crate::unsee::Unsee;
 use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::sync::Arc;
+pub mod code;
+use code::Code;
 
 #[derive(Clone)]
 pub enum OP {
@@ -12,27 +23,62 @@ pub enum OP {
     Le,
 }
 
+impl OP {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Sub => "-",
+            Mul => "*",
+            Div => "/",
+            Le => "<",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Exp {
-    Call(String, Vec<Exp>),
-    Oper(OP, Box<Exp>, Box<Exp>),
+    Call(String, Vec<Exp>, Span),
+    Oper(OP, Box<Exp>, Box<Exp>, Span),
     Const(Value),
-    Var(String),
+    Var(String, Span),
 }
 
-fn get_value(txt: &str) -> Option<Exp> {
-    if txt == "repcount" || txt == "#" {
-        return Some(Var("repcount".to_string()));
+/// Logo source of the expression, with every operation and every call
+/// inside another one in parentheses to show how it was parsed.
+impl Display for Exp {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Call(pr, args, _) => {
+                write!(formatter, "{pr}")?;
+                for arg in args {
+                    match arg {
+                        Call(_, a, _) if !a.is_empty() => write!(formatter, " ({arg})")?,
+                        _ => write!(formatter, " {arg}")?,
+                    }
+                }
+                Ok(())
+            }
+            Oper(op, x, y, _) => write!(formatter, "({x} {} {y})", op.symbol()),
+            Const(v) => write!(formatter, "{}", v.show()),
+            Var(s, _) => write!(formatter, ":{s}"),
+        }
     }
-    if let Some(name) = txt.strip_prefix(':') {
-        Some(Var(name.to_string()))
-    } else if let Some(name) = txt.strip_prefix('"') {
-        Some(Const(Value::Str(name.to_string())))
-    } else {
-        match txt.parse() {
-            Ok(n) => Some(Const(Value::Num(n))),
-            Err(_) => None,
+}
+
+/// Procedure and variable names are looked up ignoring case.
+pub fn fold_case(name: &str) -> String {
+    name.to_lowercase()
+}
+
+fn get_value(tok: &Token) -> Option<Exp> {
+    let txt = &tok.text[..];
+    match tok.kind {
+        Kind::Name if txt.eq_ignore_ascii_case("repcount") || txt == "#" => {
+            Some(Var("repcount".to_string(), tok.span))
         }
+        Kind::Var => Some(Var(txt[1..].to_string(), tok.span)),
+        Kind::Word => Some(Const(Value::Str(txt[1..].to_string()))),
+        Kind::Spec | Kind::Neg | Kind::Comment => None,
+        Kind::Name | Kind::Num => read_number(txt).map(|n| Const(Value::Num(n))),
     }
 }
 
@@ -40,7 +86,7 @@ fn get_value(txt: &str) -> Option<Exp> {
 enum ExpState {
     Empty,
     Val(Exp),
-    Op(Exp, OP),
+    Op(Exp, OP, Span),
 }
 
 struct ExpParser {
@@ -57,16 +103,16 @@ impl ExpParser {
     fn can_eat_val(&self) -> bool {
         use ExpState::*;
         match self.state {
-            Empty | Op(_, _) => true,
+            Empty | Op(_, _, _) => true,
             Val(_) => false,
         }
     }
 
-    fn shift_op(&mut self, op: OP) -> bool {
+    fn shift_op(&mut self, op: OP, span: Span) -> bool {
         use ExpState::*;
         match self.state.clone() {
             Val(v) => {
-                self.state = Op(v, op);
+                self.state = Op(v, op, span);
                 true
             }
             _ => false,
@@ -80,103 +126,132 @@ impl ExpParser {
                 self.state = Val(rhs);
                 true
             }
-            Op(v, op) => {
-                self.state = Val(Oper(op, Box::new(v), Box::new(rhs)));
+            Op(v, op, span) => {
+                self.state = Val(Oper(op, Box::new(v), Box::new(rhs), span));
                 true
             }
             _ => false,
         }
     }
 
-    fn get_value(self) -> Exp {
+    fn get_value(self) -> Result<Exp, LogoError> {
         use ExpState::*;
         match self.state {
-            Val(v) => v,
-            Empty => panic!("I do not have value!"),
-            Op(_, _) => panic!("Expected second argument"),
+            Val(v) => Ok(v),
+            Empty => Err(LogoError::NotEnoughInputs(String::new())),
+            Op(_, op, span) => {
+                Err(LogoError::NotEnoughInputs(op.symbol().to_string()).at(span.start))
+            }
         }
     }
 }
 
-fn parse_expr(procs: &HashMap<String, usize>, iter: &mut Unsee<&str>) -> Exp {
-    let mut parser = ExpParser::new();
-    loop {
-        match iter.next() {
-            Some("(") => {
-                if !parser.can_eat_val() {
-                    iter.unsee("(");
-                    break parser.get_value();
-                }
-                let x = parse_expr(procs, iter);
-                if iter.next() != Some(")") {
-                    panic!("Where is close bracket `)`?");
-                }
-                parser.shift_val(x);
-            }
-            Some("[") => {
-                // TODO: nested lists
-                if !parser.can_eat_val() {
-                    iter.unsee("[");
-                    break parser.get_value();
-                }
-                let mut list = vec![];
-                let x = loop {
-                    match iter.next() {
-                        Some("]") => break Const(Value::List(list)),
-                        Some(x) => list.push(x.to_string()),
-                        None => panic!(),
-                    }
-                };
-                parser.shift_val(x);
-            }
-            Some("*") => {
-                parser.shift_op(Mul);
-            }
-            Some("-") => {
-                parser.shift_op(Sub);
-            }
-            Some("/") => {
-                parser.shift_op(Div);
-            }
-            Some("<") => {
-                parser.shift_op(Le);
-            }
-            Some(txt) => {
-                if !parser.can_eat_val() {
-                    iter.unsee(txt);
-                    break parser.get_value();
+fn parse_inputs(
+    procs: &HashMap<String, usize>,
+    iter: &mut Unsee<&Token>,
+    name: &Token,
+    n: usize,
+) -> Result<Vec<Exp>, LogoError> {
+    (0..n)
+        .map(|_| {
+            parse_expr(procs, iter).map_err(|e| match e {
+                LogoError::NotEnoughInputs(s) if s.is_empty() => {
+                    LogoError::NotEnoughInputs(name.text.clone()).at(name.pos())
                 }
-                match get_value(txt) {
-                    Some(x) => {
-                        let ok = parser.shift_val(x);
-
-                        if !ok {
-                            iter.unsee(txt);
-                            return parser.get_value();
-                        }
+                e => e,
+            })
+        })
+        .collect()
+}
+
+fn binary_op(tok: &Token) -> Option<OP> {
+    match &tok.text[..] {
+        "*" if tok.kind == Kind::Spec => Some(Mul),
+        "-" if tok.kind == Kind::Spec => Some(Sub),
+        "/" if tok.kind == Kind::Spec => Some(Div),
+        "<" if tok.kind == Kind::Spec => Some(Le),
+        _ => None,
+    }
+}
+
+/// Parses a single operand starting with `tok`, without infix operators.
+fn parse_value(
+    procs: &HashMap<String, usize>,
+    iter: &mut Unsee<&Token>,
+    tok: &Token,
+) -> Result<Exp, LogoError> {
+    if tok.is("(") {
+        let x = parse_expr(procs, iter)?;
+        return match iter.next() {
+            Some(t) if t.is(")") => Ok(x),
+            _ => Err(LogoError::Unexpected("unmatched (".to_string()).at(tok.pos())),
+        };
+    }
+    if tok.is("[") {
+        // Lists inside keep their brackets, to be read when the list is run.
+        let mut list = vec![];
+        let mut depth = 0;
+        return loop {
+            match iter.next() {
+                Some(t) if t.is("]") && depth == 0 => break Ok(Const(Value::from(list))),
+                Some(t) => {
+                    if t.is("[") {
+                        depth += 1;
+                    } else if t.is("]") {
+                        depth -= 1;
                     }
-                    None => match procs.get(txt) {
-                        Some(n) => {
-                            let args = (0..*n).map(|_| parse_expr(procs, iter)).collect();
-                            parser.shift_val(Exp::Call(txt.to_string(), args));
-                        }
-                        None => {
-                            iter.unsee(txt);
-                            return parser.get_value();
-                        }
-                    },
+                    list.push(t.clone());
                 }
+                None => break Err(LogoError::Unexpected("unmatched [".to_string()).at(tok.pos())),
             }
-            None => return parser.get_value(),
+        };
+    }
+    if tok.kind == Kind::Neg {
+        let x = match iter.next() {
+            Some(t) => parse_value(procs, iter, t)?,
+            None => return Err(LogoError::NotEnoughInputs("-".to_string()).at(tok.pos())),
+        };
+        return Ok(Exp::Call("minus".to_string(), vec![x], tok.span));
+    }
+    if let Some(x) = get_value(tok) {
+        return Ok(x);
+    }
+    let txt = &tok.text[..];
+    match procs.get(&fold_case(txt)) {
+        Some(n) => {
+            let args = parse_inputs(procs, iter, tok, *n)?;
+            Ok(Exp::Call(txt.to_string(), args, tok.span))
         }
+        None if tok.kind == Kind::Spec => Err(LogoError::Unexpected(txt.to_string()).at(tok.pos())),
+        None => Err(LogoError::unknown(txt, procs.keys()).at(tok.pos())),
+    }
+}
+
+fn parse_expr(procs: &HashMap<String, usize>, iter: &mut Unsee<&Token>) -> Result<Exp, LogoError> {
+    let mut parser = ExpParser::new();
+    loop {
+        let Some(tok) = iter.next() else {
+            return parser.get_value();
+        };
+        if let Some(op) = binary_op(tok) {
+            if !parser.shift_op(op.clone(), tok.span) {
+                return Err(LogoError::NotEnoughInputs(op.symbol().to_string()).at(tok.pos()));
+            }
+            continue;
+        }
+        if !parser.can_eat_val() {
+            iter.unsee(tok);
+            return parser.get_value();
+        }
+        let x = parse_value(procs, iter, tok)?;
+        parser.shift_val(x);
     }
 }
 
-#[derive(Clone)]
 pub struct Procedure {
     name: String,
     vars: Vec<String>,
-    body: Vec<String>,
+    body: Arc<Code>,
 }
 
 impl Procedure {
@@ -184,8 +259,8 @@ impl Procedure {
         &self.name
     }
 
-    pub fn get_body(&self) -> Vec<&str> {
-        self.body.iter().map(AsRef::as_ref).collect()
+    pub fn get_body(&self) -> &Arc<Code> {
+        &self.body
     }
 
     pub fn get_argv(&self) -> Vec<&str> {
@@ -197,54 +272,110 @@ impl Procedure {
     }
 }
 
-fn procedure_args(iter: &mut Unsee<&str>) -> Vec<String> {
+fn procedure_args(iter: &mut Unsee<&Token>) -> Vec<String> {
     let mut vars = vec![];
-    loop {
-        let txt = iter.next().unwrap();
-        if let Some(name) = txt.strip_prefix(':') {
-            vars.push(name.to_string());
+    while let Some(tok) = iter.next() {
+        if tok.kind == Kind::Var {
+            vars.push(tok.text[1..].to_string());
         } else {
-            iter.unsee(txt);
+            iter.unsee(tok);
             break;
         }
     }
     vars
 }
 
-fn procedure_body(iter: &mut Unsee<&str>) -> Vec<String> {
+fn procedure_body(iter: &mut Unsee<&Token>, name: &Token) -> Result<Vec<Token>, LogoError> {
     let mut body = vec![];
     loop {
         match iter.next() {
-            Some("end") | Some("END") => break,
-            None => panic!("Expceted 'end'"),
-            Some(txt) => body.push(txt.to_string()),
+            Some(t) if is_keyword(t, "end") => break,
+            None => return Err(LogoError::MissingEnd(name.text.clone()).at(name.pos())),
+            Some(t) => body.push(t.clone()),
         }
     }
-    body
+    Ok(body)
 }
 
-fn parse_procedure(iter: &mut Unsee<&str>) -> Procedure {
-    let name = iter.next().unwrap().to_string();
+fn parse_procedure(iter: &mut Unsee<&Token>, to: &Token) -> Result<Procedure, LogoError> {
+    let name = iter
+        .next()
+        .ok_or_else(|| LogoError::NotEnoughInputs(to.text.clone()).at(to.pos()))?;
     let vars = procedure_args(iter);
-    let body = procedure_body(iter);
-    Procedure { name, vars, body }
+    let body = procedure_body(iter, name)?;
+    Ok(Procedure {
+        name: name.text.clone(),
+        vars,
+        body: Arc::new(Code::new(body)),
+    })
+}
+
+/// A `to` definition found by scanning the tokens, before it is parsed.
+pub struct Definition<'a> {
+    pub name: &'a Token,
+    pub argv: &'a [Token],
+    pub body: &'a [Token],
+}
+
+fn is_keyword(tok: &Token, keyword: &str) -> bool {
+    tok.kind == Kind::Name && tok.text.eq_ignore_ascii_case(keyword)
+}
+
+/// All `to` definitions in `tokens`, also those inside other definitions
+/// or lists.
+pub fn scan_definitions(tokens: &[Token]) -> Vec<Definition<'_>> {
+    let mut defs = vec![];
+    for (i, to) in tokens.iter().enumerate() {
+        let Some(name) = tokens.get(i + 1).filter(|_| is_keyword(to, "to")) else {
+            continue;
+        };
+        let rest = &tokens[i + 2..];
+        let n = rest.iter().take_while(|t| t.kind == Kind::Var).count();
+        let len = rest[n..]
+            .iter()
+            .take_while(|t| !is_keyword(t, "end"))
+            .count();
+        defs.push(Definition {
+            name,
+            argv: &rest[..n],
+            body: &rest[n..n + len],
+        });
+    }
+    defs
 }
 
 pub enum Stat {
-    ProcDef(Procedure),
+    ProcDef(Arc<Procedure>),
     Exp(Exp),
 }
 
-pub fn parse_statement(procs: &HashMap<String, usize>, iter: &mut Unsee<&str>) -> Option<Stat> {
+impl Display for Stat {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Stat::ProcDef(proc) => {
+                write!(formatter, "to {}", proc.name)?;
+                proc.vars
+                    .iter()
+                    .try_for_each(|v| write!(formatter, " :{v}"))
+            }
+            Stat::Exp(e) => write!(formatter, "{e}"),
+        }
+    }
+}
+
+pub fn parse_statement(
+    procs: &HashMap<String, usize>,
+    iter: &mut Unsee<&Token>,
+) -> Result<Option<Stat>, LogoError> {
     match iter.next() {
-        None => None,
-        Some("to") | Some("TO") | Some("To") => {
-            let proc = parse_procedure(iter);
-            Some(Stat::ProcDef(proc))
-        }
-        Some(txt) => {
-            iter.unsee(txt);
-            Some(Stat::Exp(parse_expr(procs, iter)))
+        None => Ok(None),
+        Some(tok) if is_keyword(tok, "to") => {
+            let proc = parse_procedure(iter, tok)?;
+            Ok(Some(Stat::ProcDef(Arc::new(proc))))
+        }
+        Some(tok) => {
+            iter.unsee(tok);
+            Ok(Some(Stat::Exp(parse_expr(procs, iter)?)))
         }
     }
 }
@@ -0,0 +1,159 @@
+use crate::error::LogoError;
+use crate::interpretator::bytecode;
+use crate::interpretator::bytecode::Chunk;
+use crate::interpretator::Context;
+use crate::parser::parse_statement;
+use crate::parser::Exp;
+use crate::parser::Stat;
+use crate::tokenizer::Token;
+use crate::unsee::Unsee;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::OnceLock;
+
+/// Tokens of a procedure body or a list, with the statements parsed from
+/// them so far.
+///
+/// Statements are parsed lazily, one at a time right before they run, like
+/// the interpreter always did, but only once: later runs reuse the cached
+/// trees. Parsing depends on the arities of procedures, so the cache is
+/// dropped when the `generation` of the signatures changes. So is the
+/// bytecode of the whole code, which the VM runs.
+pub struct Code {
+    tokens: Vec<Token>,
+    parsed: Mutex<Parsed>,
+}
+
+#[derive(Default)]
+struct Parsed {
+    generation: u64,
+    stats: Vec<Arc<Statement>>,
+    chunk: Option<Arc<Chunk>>,
+}
+
+pub struct Statement {
+    pub stat: Stat,
+    /// Index of the token following the statement.
+    pub next: usize,
+    chunk: OnceLock<Arc<Chunk>>,
+}
+
+impl Statement {
+    /// Bytecode of the expression `exp` of this statement, compiled once.
+    pub fn chunk(&self, exp: &Exp, ctx: &Context) -> &Arc<Chunk> {
+        self.chunk
+            .get_or_init(|| Arc::new(bytecode::compile(exp, ctx)))
+    }
+}
+
+impl Code {
+    pub fn new(tokens: Vec<Token>) -> Code {
+        Code {
+            tokens,
+            parsed: Mutex::default(),
+        }
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The words of a list as written, tokens with no space between them,
+    /// like the `-` and `b` of `-b`, making one word. Brackets are words of
+    /// their own.
+    pub fn words(&self) -> Vec<String> {
+        let mut words: Vec<String> = vec![];
+        let mut last: Option<&Token> = None;
+        for tok in &self.tokens {
+            let bracket = |t: &Token| t.is("[") || t.is("]");
+            match (last, words.last_mut()) {
+                (Some(prev), Some(word))
+                    if prev.span.end == tok.span.start && !bracket(prev) && !bracket(tok) =>
+                {
+                    word.push_str(&tok.text)
+                }
+                _ => words.push(tok.text.clone()),
+            }
+            last = Some(tok);
+        }
+        words
+    }
+
+    /// The cache, dropped if it is for another `generation`.
+    fn parsed(&self, generation: u64) -> MutexGuard<'_, Parsed> {
+        let mut parsed = self.parsed.lock().unwrap();
+        if parsed.generation != generation {
+            *parsed = Parsed {
+                generation,
+                ..Parsed::default()
+            };
+        }
+        parsed
+    }
+
+    /// Bytecode of all the statements, compiled once for the `generation`
+    /// of `ctx`.
+    pub fn chunk(self: &Arc<Code>, ctx: &Context) -> Arc<Chunk> {
+        if let Some(chunk) = &self.parsed(ctx.generation()).chunk {
+            return chunk.clone();
+        }
+        // Compiled unlocked, as it parses the statements.
+        let chunk = Arc::new(bytecode::compile_code(self, ctx));
+        self.parsed(ctx.generation()).chunk = Some(chunk.clone());
+        chunk
+    }
+
+    /// The `i`-th statement, which starts at token `at`. `None` at the end
+    /// of the code. A freshly parsed statement goes through `prepare` before
+    /// it is cached.
+    pub fn statement(
+        &self,
+        signs: &HashMap<String, usize>,
+        generation: u64,
+        i: usize,
+        at: usize,
+        prepare: impl FnOnce(Stat) -> Stat,
+    ) -> Result<Option<Arc<Statement>>, LogoError> {
+        if let Some(stat) = self.parsed(generation).stats.get(i) {
+            return Ok(Some(stat.clone()));
+        }
+        // Parsed and prepared unlocked, as `prepare` may parse other code.
+        let mut iter = Unsee::wrap(self.tokens[at..].iter());
+        let Some(stat) = parse_statement(signs, &mut iter)? else {
+            return Ok(None);
+        };
+        let stat = Arc::new(Statement {
+            stat: prepare(stat),
+            next: at + iter.consumed(),
+            chunk: OnceLock::new(),
+        });
+        // Statements before `i` were parsed for older signatures.
+        let mut parsed = self.parsed(generation);
+        if parsed.stats.len() == i {
+            parsed.stats.push(stat.clone());
+        }
+        Ok(Some(stat))
+    }
+}
+
+impl PartialEq for Code {
+    fn eq(&self, other: &Code) -> bool {
+        self.tokens == other.tokens
+    }
+}
+
+impl PartialOrd for Code {
+    fn partial_cmp(&self, other: &Code) -> Option<Ordering> {
+        self.tokens.partial_cmp(&other.tokens)
+    }
+}
+
+impl fmt::Debug for Code {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.tokens.fmt(formatter)
+    }
+}
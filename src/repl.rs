@@ -0,0 +1,107 @@
+use logo::incomplete;
+use logo::Config;
+use logo::Interpreter;
+use logo::Value;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::Editor;
+use rustyline::Helper;
+use std::env;
+use std::path::PathBuf;
+
+const PROMPT: &str = "? ";
+/// Prompt for the following lines of a definition or a list.
+const CONTINUE: &str = "> ";
+
+/// Completes names of primitives and procedures.
+struct Names(Vec<String>);
+
+impl Completer for Names {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || "[]()".contains(c))
+            .map_or(0, |i| i + 1);
+        let word = line[start..pos].to_lowercase();
+        let names = self.0.iter().filter(|n| n.starts_with(&word));
+        Ok((start, names.cloned().collect()))
+    }
+}
+
+impl Hinter for Names {
+    type Hint = String;
+}
+
+impl Highlighter for Names {}
+
+impl Validator for Names {}
+
+impl Helper for Names {}
+
+fn history() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".logo_history"))
+}
+
+/// Reads lines and runs them in one context until `bye` or end of input.
+pub fn repl() -> rustyline::Result<()> {
+    let mut logo = Interpreter::new(Config::default());
+    let mut rl: Editor<Names, DefaultHistory> = Editor::new()?;
+    let history = history();
+    if let Some(file) = &history {
+        // There is no history the first time.
+        let _ = rl.load_history(file);
+    }
+    loop {
+        let mut names: Vec<String> = ["to", "end", "bye"].map(String::from).to_vec();
+        names.extend(logo.names().map(String::from));
+        names.sort();
+        rl.set_helper(Some(Names(names)));
+
+        let mut src = String::new();
+        let mut prompt = PROMPT;
+        loop {
+            match rl.readline(prompt) {
+                Ok(line) => src += &line,
+                Err(ReadlineError::Interrupted) => {
+                    src.clear();
+                    break;
+                }
+                Err(ReadlineError::Eof) if src.is_empty() => src += "bye",
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e),
+            }
+            src.push('\n');
+            if !incomplete(&src) {
+                break;
+            }
+            prompt = CONTINUE;
+        }
+        if src.trim().is_empty() {
+            continue;
+        }
+        rl.add_history_entry(src.trim_end())?;
+        if src.trim().eq_ignore_ascii_case("bye") {
+            break;
+        }
+        match logo.eval(&src) {
+            Ok(Value::Void) => (),
+            Ok(v) => println!("{v}"),
+            Err(e) => eprintln!("{}", e.report("input")),
+        }
+    }
+    if let Some(file) = &history {
+        // Losing the history is not worth an error on the way out.
+        let _ = rl.save_history(file);
+    }
+    Ok(())
+}
@@ -1,29 +1,179 @@
+use crate::error::LogoError;
+use ab_glyph::FontVec;
+use animation::Animation;
+use animation::AnimationOptions;
+use canvas::Canvas;
+use canvas::Event;
+use canvas::LineCap;
+use canvas::LineJoin;
+use canvas::LineStyle;
+use canvas::PenMode;
+use canvas::Point;
+use canvas::Recording;
+use gif_canvas::Gif;
+use gif_canvas::GifOptions;
+use pdf::Pdf;
+use pdf::PdfOptions;
+use raster::PngOptions;
+use raster::Raster;
+use std::env;
 use std::f32::consts::PI;
-use svg::node;
-use svg::node::element::path::Data;
-use svg::node::element::Path;
-use svg::Document;
+use std::fs;
+use std::str::FromStr;
+use svg_canvas::SvgCanvas;
 
+pub mod animation;
+pub mod canvas;
+mod flood;
+pub mod gif_canvas;
+pub mod pdf;
+pub mod raster;
+pub mod svg_canvas;
+
+/// How the drawing is saved in each format.
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub struct SaveOptions {
+    pub png: PngOptions,
+    pub pdf: PdfOptions,
+    pub gif: GifOptions,
+    /// Saves SVG drawing itself rather than finished.
+    pub animation: Option<AnimationOptions>,
+}
+
+/// The font for labels when `$LOGO_FONT` doesn't name one, so that labels
+/// look the same on every machine.
+const FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+/// The font for labels, for the canvases which don't leave the drawing of
+/// text to someone else.
+fn font() -> FontVec {
+    let custom = env::var("LOGO_FONT").ok();
+    let read = custom.and_then(|f| FontVec::try_from_vec(fs::read(f).ok()?).ok());
+    read.unwrap_or_else(|| FontVec::try_from_vec(FONT.to_vec()).expect("bundled font"))
+}
+
+/// The color named as in SVG, or `None` for a name SVG doesn't know.
+fn color(name: &str) -> Option<svgtypes::Color> {
+    svgtypes::Color::from_str(name).ok()
+}
+
+/// A shape the turtle can take, by name. Shapes are the corners of a
+/// polygon around the turtle at the origin, heading up the y axis.
+pub fn shape(name: &str) -> Option<Vec<Point>> {
+    let corners = |xy: &[(f32, f32)]| xy.iter().map(|&(x, y)| Point { x, y }).collect();
+    match name {
+        // As in UCBLogo.
+        "triangle" => Some(corners(&[(0., 15.), (7.5, 0.), (-7.5, 0.)])),
+        "arrow" => Some(corners(&[
+            (0., 15.),
+            (8., 5.),
+            (3., 5.),
+            (3., -10.),
+            (-3., -10.),
+            (-3., 5.),
+            (-8., 5.),
+        ])),
+        "circle" => Some(
+            (0..24)
+                .map(|i| {
+                    let (sin, cos) = (i as f32 * PI / 12.).sin_cos();
+                    Point {
+                        x: 10. * cos,
+                        y: 10. * sin,
+                    }
+                })
+                .collect(),
+        ),
+        "turtle" => {
+            let right = [
+                (0., 16.),
+                (3., 14.),
+                (3., 10.),
+                (6., 12.),
+                (8., 9.),
+                (6., 6.),
+                (7., 0.),
+                (6., -5.),
+                (8., -8.),
+                (5., -9.),
+                (3., -7.),
+                (1., -8.),
+            ];
+            let left = right[1..].iter().rev().map(|&(x, y)| (-x, y));
+            let outline: Vec<_> = right.into_iter().chain([(0., -12.)]).chain(left).collect();
+            Some(corners(&outline))
+        }
+        _ => None,
+    }
+}
+
+/// Corners of `shape` worn by the turtle at `at` heading towards `angle`.
+fn sprite(at: Point, angle: f32, shape: &[Point]) -> Vec<Point> {
+    let (sin, cos) = angle.sin_cos();
+    // The turtle's y axis points ahead and its x axis to its right.
+    let corner = |p: &Point| Point {
+        x: at.x + p.y * cos - p.x * sin,
+        y: at.y + p.y * sin + p.x * cos,
+    };
+    shape.iter().map(corner).collect()
+}
+
+/// A shape being traced by `filled`.
+struct Filling {
+    /// Where its fill goes among the events, under the lines tracing it.
+    at: usize,
+    /// Its outline, a new part starting wherever the turtle moves without
+    /// drawing.
+    subpaths: Vec<Vec<Point>>,
+}
+
+/// Where the turtle is and what it does, kept as a list of events for the
+/// canvases it is drawn on.
 pub struct Robot {
-    document: svg::Document,
-    color: String,
-    data: Option<Data>,
+    recording: Recording,
+    down: bool,
     x: f32,
     y: f32,
     angle: f32,
     labelheight: f32,
+    style: LineStyle,
+    mode: PenMode,
+    shown: bool,
+    shape: Vec<Point>,
+    floodcolor: String,
+    /// The shapes being traced, innermost last.
+    filling: Vec<Filling>,
+    /// The drawing in pixels, for `fill` to find regions in, with the
+    /// number of events drawn on it.
+    shadow: Option<(Raster, usize)>,
 }
 
 impl Robot {
     pub fn new() -> Robot {
+        let mut recording = Recording::default();
+        recording.pen_down(Point { x: 500., y: 500. });
         Robot {
-            document: Document::new(),
-            color: "black".to_string(),
-            data: Some(Data::new().move_to((500, 500))),
-            x: 250.0,
-            y: 250.0,
+            recording,
+            down: true,
+            x: 500.0,
+            y: 500.0,
             angle: -PI / 2.0,
             labelheight: 100.,
+            style: LineStyle::default(),
+            mode: PenMode::Paint,
+            shown: true,
+            shape: shape("triangle").unwrap(),
+            floodcolor: "black".to_string(),
+            filling: vec![],
+            shadow: None,
+        }
+    }
+
+    fn pos(&self) -> Point {
+        Point {
+            x: self.x,
+            y: self.y,
         }
     }
 
@@ -34,36 +184,147 @@ impl Robot {
     pub fn forward(&mut self, m: f32) {
         let x: f32 = self.angle.cos() * m;
         let y: f32 = self.angle.sin() * m;
-        self.data = self.data.take().map(|d| d.line_by((x, y)));
         self.x += x;
         self.y += y;
+        match self.down {
+            true => self.recording.line_by(x, y),
+            false => self.recording.move_to(self.pos()),
+        }
+        self.trace(self.down);
+    }
+
+    /// Adds where the turtle is to the shapes being traced, joined by a
+    /// line to where it was if `line`.
+    fn trace(&mut self, line: bool) {
+        let pos = self.pos();
+        for filling in &mut self.filling {
+            match filling.subpaths.last_mut() {
+                Some(subpath) if line => subpath.push(pos),
+                _ => filling.subpaths.push(vec![pos]),
+            }
+        }
+    }
+
+    /// Starts tracing a shape to fill.
+    pub fn begin_fill(&mut self) {
+        self.flush_path();
+        self.filling.push(Filling {
+            at: self.recording.events().len(),
+            subpaths: vec![vec![self.pos()]],
+        });
     }
 
+    /// Fills the shape traced since the matching `begin_fill` with `color`.
+    pub fn end_fill(&mut self, color: String) {
+        let Some(filling) = self.filling.pop() else {
+            return;
+        };
+        let subpaths: Vec<_> = filling
+            .subpaths
+            .into_iter()
+            .filter(|s| s.len() > 2)
+            .collect();
+        if !subpaths.is_empty() {
+            let filled = Event::Filled { subpaths, color };
+            self.recording.insert(filling.at, filled);
+            self.recording.end_fill();
+            // It goes under what the shadow has drawn already.
+            if self.shadow.as_ref().is_some_and(|(_, n)| *n > filling.at) {
+                self.shadow = None;
+            }
+        }
+    }
+
+    /// Floods the region around the turtle with the flood color.
+    pub fn fill(&mut self) {
+        self.flush_path();
+        let pos = self.pos();
+        if self.shadow.is_none() {
+            let options = PngOptions {
+                width: 1000,
+                ..PngOptions::default()
+            };
+            self.shadow = Raster::new(options).ok().map(|r| (r, 0));
+        }
+        let Some((shadow, drawn)) = &mut self.shadow else {
+            return;
+        };
+        for event in &self.recording.events()[*drawn..] {
+            event.draw(shadow);
+        }
+        *drawn = self.recording.events().len();
+        let Some(region) = shadow.region(pos) else {
+            return;
+        };
+        // The shadow has a pixel a step.
+        let outline = region.outline(1.);
+        self.recording.flood(pos, &outline, &self.floodcolor);
+    }
+
+    pub fn setfloodcolor(&mut self, color: String) {
+        self.floodcolor = color;
+    }
+
+    pub fn floodcolor(&self) -> &str {
+        &self.floodcolor
+    }
+
+    /// Ends the line being drawn, going on from where the turtle is.
     fn flush_path(&mut self) {
-        if let Some(data) = self.data.clone() {
-            let path = Path::new()
-                .set("fill", "none")
-                .set("stroke", self.color.clone())
-                .set("stroke-width", 1)
-                .set("d", data);
-            self.document = self.document.clone().add(path);
-            self.data = Some(Data::new().move_to((self.x, self.y)));
+        if self.down {
+            self.recording.pen_down(self.pos());
         }
     }
 
     pub fn setpencolor(&mut self, color: String) {
         self.flush_path();
-        self.color = color;
+        self.recording.set_color(&color);
     }
 
-    pub fn penup(&mut self) {
+    fn set_line_style(&mut self, style: LineStyle) {
         self.flush_path();
-        self.data = None;
+        self.style = style;
+        self.recording.set_line_style(style);
+    }
+
+    pub fn setpensize(&mut self, width: f32) {
+        self.set_line_style(LineStyle {
+            width,
+            ..self.style
+        });
+    }
+
+    pub fn pensize(&self) -> f32 {
+        self.style.width
+    }
+
+    pub fn setlinecap(&mut self, cap: LineCap) {
+        self.set_line_style(LineStyle { cap, ..self.style });
+    }
+
+    pub fn setlinejoin(&mut self, join: LineJoin) {
+        self.set_line_style(LineStyle { join, ..self.style });
+    }
+
+    pub fn penup(&mut self) {
+        self.recording.pen_up();
+        self.down = false;
     }
 
     pub fn pendown(&mut self) {
-        self.flush_path();
-        self.data = Some(Data::new().move_to((self.x, self.y)));
+        self.recording.pen_down(self.pos());
+        self.down = true;
+    }
+
+    /// Puts the pen down in `mode`.
+    pub fn setpenmode(&mut self, mode: PenMode) {
+        self.pendown();
+        self.mode = mode;
+        self.recording.set_pen_mode(mode);
+    }
+
+    pub fn penmode(&self) -> PenMode {
+        self.mode
     }
 
     pub fn back(&mut self, m: f32) {
@@ -72,39 +333,109 @@ impl Robot {
 
     pub fn left(&mut self, t: f32) {
         self.angle -= t;
+        self.recording.turn(self.angle);
     }
 
     pub fn right(&mut self, t: f32) {
         self.angle += t;
+        self.recording.turn(self.angle);
     }
 
-    pub fn plot(&mut self) -> svg::Document {
-        self.flush_path();
+    /// Everything drawn so far, the line being drawn not yet ended.
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Draws everything drawn so far on `canvas`, then the turtle if it
+    /// is shown.
+    pub fn draw(&self, canvas: &mut dyn Canvas) {
+        self.recording.replay(canvas);
+        // Ends the line being drawn on the canvas only.
+        if self.down {
+            canvas.pen_down(self.pos());
+        }
+        if self.shown {
+            // The turtle itself is always painted.
+            if self.mode != PenMode::Paint {
+                canvas.set_pen_mode(PenMode::Paint);
+            }
+            canvas.turtle(&sprite(self.pos(), self.angle, &self.shape));
+        }
+    }
+
+    pub fn plot(&self) -> svg::Document {
+        let mut canvas = SvgCanvas::new();
+        self.draw(&mut canvas);
+        canvas.document()
+    }
 
-        self.document.clone().set("viewBox", (0, 0, 1000, 1000))
+    pub fn animation(&self, options: AnimationOptions) -> svg::Document {
+        let mut animation = Animation::new(options);
+        self.draw(&mut animation);
+        animation.document()
+    }
+
+    pub fn png(&self, options: PngOptions) -> Result<Vec<u8>, LogoError> {
+        let mut raster = Raster::new(options)?;
+        self.draw(&mut raster);
+        raster.png()
+    }
+
+    pub fn gif(&self, options: GifOptions) -> Result<Vec<u8>, LogoError> {
+        let mut gif = Gif::new(options)?;
+        self.draw(&mut gif);
+        gif.finish()
+    }
+
+    pub fn pdf(&self, options: PdfOptions) -> Vec<u8> {
+        let mut pdf = Pdf::new(options);
+        pdf.page(|page| self.draw(page));
+        pdf.finish()
     }
 
     pub fn setpos(&mut self, x: f32, y: f32) {
-        if let Some(data) = self.data.clone() {
-            self.data = Some(data.move_to((x, y)));
-        }
         self.x = x;
         self.y = y;
+        self.recording.move_to(self.pos());
+        self.trace(false);
     }
 
     pub fn label(&mut self, s: String) {
-        let txt = node::element::Text::new()
-            .set("x", self.x)
-            .set("y", self.y)
-            .set("fill", &*self.color)
-            .set(
-                "transform",
-                format!("rotate({} {},{})", 180.0 * self.angle / PI, self.x, self.y),
-            )
-            .set("style", format!("font: {}px sans-serif;", self.labelheight))
-            .add(node::Text::new(s));
+        let at = self.pos();
+        self.recording.text(at, self.angle, self.labelheight, &s);
+    }
+
+    /// Pauses the animation, not at all for a negative time.
+    pub fn wait(&mut self, seconds: f32) {
+        self.recording.wait(seconds.max(0.));
+    }
 
-        self.document = self.document.clone().add(txt);
+    pub fn showturtle(&mut self) {
+        self.shown = true;
+        self.recording.show_turtle(true);
+    }
+
+    pub fn hideturtle(&mut self) {
+        self.shown = false;
+        self.recording.show_turtle(false);
+    }
+
+    pub fn shown(&self) -> bool {
+        self.shown
+    }
+
+    pub fn setshape(&mut self, shape: Vec<Point>) {
+        self.recording.set_shape(&shape);
+        self.shape = shape;
+    }
+
+    /// Draws the shape of the turtle where it is.
+    pub fn stamp(&mut self) {
+        self.flush_path();
+        let outline = sprite(self.pos(), self.angle, &self.shape);
+        self.recording.polygon(&outline);
+        // The line goes on from the turtle whatever drew the stamp.
+        self.flush_path();
     }
 
     pub fn setlabelheight(&mut self, h: f32) {
@@ -113,7 +444,7 @@ impl Robot {
 
     pub fn clean(&mut self) {
         self.flush_path();
-        self.document = Document::new();
+        self.recording.clear();
     }
 
     pub fn clearscreen(&mut self) {
@@ -0,0 +1,295 @@
+use crate::robot::canvas::Canvas;
+use crate::robot::canvas::LineStyle;
+use crate::robot::canvas::PenMode;
+use crate::robot::canvas::Point;
+use crate::robot::svg_canvas::backdrop;
+use crate::robot::svg_canvas::filled;
+use crate::robot::svg_canvas::label;
+use crate::robot::svg_canvas::path;
+use crate::robot::svg_canvas::polygon;
+use std::mem;
+use svg::node::element::path::Data;
+use svg::node::element::Animate;
+use svg::node::element::Element;
+use svg::node::element::Group;
+use svg::node::element::Path;
+use svg::node::element::Text;
+use svg::Document;
+use svg::Node;
+
+/// How fast the drawing is traced.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct AnimationOptions {
+    /// Steps the turtle walks in a second.
+    pub speed: f32,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> AnimationOptions {
+        AnimationOptions { speed: 200. }
+    }
+}
+
+/// A `<set>` of `visibility` to `to` at `time`.
+fn visibility(to: &str, time: f32) -> Element {
+    let mut set = Element::new("set");
+    set.assign("attributeName", "visibility");
+    set.assign("to", to);
+    set.assign("begin", format!("{time}s"));
+    set.assign("fill", "freeze");
+    set
+}
+
+/// A line being traced while the pen stays down.
+struct Line {
+    data: Data,
+    /// When the turtle started it.
+    begin: f32,
+    /// Its length so far.
+    length: f32,
+    /// How long it was at each time the turtle moved or stopped.
+    keys: Vec<(f32, f32)>,
+}
+
+/// What is drawn: fills are kept apart until their outline is traced,
+/// when they are shown.
+enum Drawn {
+    Path(Path),
+    Label(Text),
+    Fill(Path, Option<f32>),
+}
+
+/// What is drawn between two clears, hidden at the second one.
+#[derive(Default)]
+struct Layer {
+    drawn: Vec<Drawn>,
+    hidden: Option<f32>,
+}
+
+/// Draws an SVG document in which each line is traced and each label
+/// shown at the time the turtle drew it.
+pub struct Animation {
+    speed: f32,
+    /// Seconds since the turtle started.
+    time: f32,
+    /// What was drawn before each time the screen was cleared, then what
+    /// has been drawn since.
+    layers: Vec<Layer>,
+    /// Where in `layers` the fills whose outline is being traced are,
+    /// innermost last.
+    fills: Vec<(usize, usize)>,
+    /// Whether any line is drawn in reverse.
+    reversed: bool,
+    color: String,
+    style: LineStyle,
+    mode: PenMode,
+    down: bool,
+    /// Where the pen is.
+    pen: Point,
+    line: Option<Line>,
+}
+
+impl Animation {
+    pub fn new(options: AnimationOptions) -> Animation {
+        Animation {
+            speed: options.speed,
+            time: 0.,
+            layers: vec![Layer::default()],
+            fills: vec![],
+            reversed: false,
+            color: "black".to_string(),
+            style: LineStyle::default(),
+            mode: PenMode::Paint,
+            down: false,
+            pen: Point { x: 0., y: 0. },
+            line: None,
+        }
+    }
+
+    /// Walks `distance` steps.
+    fn walk(&mut self, distance: f32) {
+        self.time += distance / self.speed;
+        if let Some(line) = &mut self.line {
+            line.keys.push((self.time, line.length));
+        }
+    }
+
+    fn layer(&mut self) -> &mut Layer {
+        self.layers.last_mut().unwrap()
+    }
+
+    fn draw(&mut self, drawn: Drawn) {
+        self.layer().drawn.push(drawn);
+    }
+
+    /// Ends the line being traced, which is drawn as one path for its
+    /// joints to show. A dash is moved along it as the turtle walked.
+    fn flush_line(&mut self) {
+        let Some(mut line) = self.line.take() else {
+            return;
+        };
+        if line.length == 0. {
+            return;
+        }
+        // It is traced by the time its last step is.
+        while line.keys.len() > 2 && line.keys[line.keys.len() - 2].1 == line.length {
+            line.keys.pop();
+        }
+        let end = line.keys.last().map_or(line.begin, |(t, _)| *t);
+        let dur = end - line.begin;
+        let at = |t: f32| match dur {
+            0. => 1.,
+            dur => (t - line.begin) / dur,
+        };
+        let join = |v: Vec<String>| v.join(";");
+        let values = line.keys.iter().map(|(_, l)| (line.length - l).to_string());
+        let times = line.keys.iter().map(|(t, _)| at(*t).to_string());
+        let trace = Animate::new()
+            .set("attributeName", "stroke-dashoffset")
+            .set("values", join(values.collect()))
+            .set("keyTimes", join(times.collect()))
+            .set("begin", format!("{}s", line.begin))
+            .set("dur", format!("{dur}s"))
+            .set("fill", "freeze");
+        self.reversed |= self.mode == PenMode::Reverse;
+        let path = path(&self.color, self.style, self.mode, line.data)
+            .set("stroke-dasharray", line.length)
+            .set("stroke-dashoffset", line.length)
+            .add(trace);
+        self.draw(Drawn::Path(path));
+    }
+
+    pub fn document(self) -> Document {
+        let mut document = Document::new();
+        if self.reversed {
+            document = document.add(backdrop());
+        }
+        let end = self.time;
+        for layer in self.layers {
+            let mut group = Group::new();
+            for drawn in layer.drawn {
+                match drawn {
+                    Drawn::Path(path) => group.append(path),
+                    Drawn::Label(text) => group.append(text),
+                    Drawn::Fill(shape, time) => group.append(
+                        shape
+                            .set("visibility", "hidden")
+                            .add(visibility("visible", time.unwrap_or(end))),
+                    ),
+                }
+            }
+            if let Some(time) = layer.hidden {
+                group = group.add(visibility("hidden", time));
+            }
+            document = document.add(group);
+        }
+        document.set("viewBox", (0, 0, 1000, 1000))
+    }
+}
+
+impl Canvas for Animation {
+    fn pen_down(&mut self, at: Point) {
+        self.flush_line();
+        self.down = true;
+        self.pen = at;
+    }
+
+    fn pen_up(&mut self) {
+        self.flush_line();
+        self.down = false;
+    }
+
+    fn move_to(&mut self, to: Point) {
+        if let Some(line) = &mut self.line {
+            line.data = mem::take(&mut line.data).move_to((to.x, to.y));
+        }
+        self.walk((to.x - self.pen.x).hypot(to.y - self.pen.y));
+        self.pen = to;
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        if self.down && self.line.is_none() {
+            self.line = Some(Line {
+                data: Data::new().move_to((self.pen.x, self.pen.y)),
+                begin: self.time,
+                length: 0.,
+                keys: vec![(self.time, 0.)],
+            });
+        }
+        let length = dx.hypot(dy);
+        if let Some(line) = &mut self.line {
+            line.data = mem::take(&mut line.data).line_by((dx, dy));
+            line.length += length;
+        }
+        self.walk(length);
+        self.pen.x += dx;
+        self.pen.y += dy;
+    }
+
+    fn set_color(&mut self, color: &str) {
+        self.color = color.to_string();
+    }
+
+    fn set_line_style(&mut self, style: LineStyle) {
+        self.style = style;
+    }
+
+    fn set_pen_mode(&mut self, mode: PenMode) {
+        self.mode = mode;
+    }
+
+    fn text(&mut self, at: Point, angle: f32, height: f32, text: &str) {
+        let text = label(at, angle, height, &self.color, text)
+            .set("visibility", "hidden")
+            .add(visibility("visible", self.time));
+        self.draw(Drawn::Label(text));
+    }
+
+    fn polygon(&mut self, corners: &[Point]) {
+        self.reversed |= self.mode == PenMode::Reverse;
+        let outline = polygon(corners, &self.color, self.style, self.mode)
+            .set("visibility", "hidden")
+            .add(visibility("visible", self.time));
+        self.draw(Drawn::Path(outline));
+    }
+
+    /// Goes under its outline, but is only shown once that is traced.
+    fn filled(&mut self, subpaths: &[Vec<Point>], color: &str) {
+        let at = (self.layers.len() - 1, self.layer().drawn.len());
+        self.fills.push(at);
+        self.draw(Drawn::Fill(filled(subpaths, color), None));
+    }
+
+    fn end_fill(&mut self) {
+        let Some((layer, i)) = self.fills.pop() else {
+            return;
+        };
+        if let Drawn::Fill(_, time) = &mut self.layers[layer].drawn[i] {
+            *time = Some(self.time);
+        }
+    }
+
+    fn flood(&mut self, _at: Point, outline: &[Vec<Point>], color: &str) {
+        let shape = filled(outline, color)
+            .set("visibility", "hidden")
+            .add(visibility("visible", self.time));
+        self.draw(Drawn::Path(shape));
+    }
+
+    fn clear(&mut self) {
+        self.flush_line();
+        if self.layer().drawn.is_empty() {
+            return;
+        }
+        self.layer().hidden = Some(self.time);
+        self.layers.push(Layer::default());
+    }
+
+    fn wait(&mut self, seconds: f32) {
+        self.time += seconds;
+        if let Some(line) = &mut self.line {
+            line.keys.push((self.time, line.length));
+        }
+    }
+}
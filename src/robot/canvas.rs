@@ -0,0 +1,363 @@
+/// A point of the picture, with `y` growing downwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// How the ends of lines are drawn, named as in SVG.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineCap {
+    /// Square, right at the end.
+    #[default]
+    Butt,
+    Round,
+    /// Square, half the pen size beyond the end.
+    Square,
+}
+
+impl LineCap {
+    pub fn from_name(name: &str) -> Option<LineCap> {
+        match name {
+            "butt" => Some(LineCap::Butt),
+            "round" => Some(LineCap::Round),
+            "square" => Some(LineCap::Square),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineCap::Butt => "butt",
+            LineCap::Round => "round",
+            LineCap::Square => "square",
+        }
+    }
+}
+
+/// How lines are joined where the turtle turns, named as in SVG.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineJoin {
+    /// Pointed, unless too sharp.
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl LineJoin {
+    pub fn from_name(name: &str) -> Option<LineJoin> {
+        match name {
+            "miter" => Some(LineJoin::Miter),
+            "round" => Some(LineJoin::Round),
+            "bevel" => Some(LineJoin::Bevel),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineJoin::Miter => "miter",
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+        }
+    }
+}
+
+/// How lines are drawn, but for their color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineStyle {
+    /// In steps.
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl Default for LineStyle {
+    fn default() -> LineStyle {
+        LineStyle {
+            width: 1.,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+        }
+    }
+}
+
+/// What the pen does to what it draws over, as in UCBLogo.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PenMode {
+    /// Draws in the pen color.
+    #[default]
+    Paint,
+    /// Draws in the color of the background, white.
+    Erase,
+    /// Inverts the colors, so that drawing the same line again takes it
+    /// away.
+    Reverse,
+}
+
+impl PenMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            PenMode::Paint => "paint",
+            PenMode::Erase => "erase",
+            PenMode::Reverse => "reverse",
+        }
+    }
+}
+
+/// Something the turtle draws on, told what happens as it happens.
+pub trait Canvas {
+    /// Puts the pen down at `at`, ending the line drawn so far: the lines
+    /// which follow start there.
+    fn pen_down(&mut self, at: Point);
+
+    /// Lifts the pen, ending the line drawn so far.
+    fn pen_up(&mut self);
+
+    /// The turtle jumps to `to` without drawing.
+    fn move_to(&mut self, to: Point);
+
+    /// Draws a line from the pen by `(dx, dy)`.
+    fn line_by(&mut self, dx: f32, dy: f32);
+
+    /// Color of what is drawn next.
+    fn set_color(&mut self, color: &str);
+
+    /// Width, ends and joints of the lines drawn next. Canvases which
+    /// draw every line alike ignore it.
+    fn set_line_style(&mut self, _style: LineStyle) {}
+
+    /// What the lines drawn next do to what they are drawn over. Canvases
+    /// which only paint ignore it.
+    fn set_pen_mode(&mut self, _mode: PenMode) {}
+
+    /// Writes `text` from `at` in the direction `angle`, in radians.
+    fn text(&mut self, at: Point, angle: f32, height: f32, text: &str);
+
+    /// Draws the closed outline through `corners`, by default as a line
+    /// of its own with the pen lifted after it.
+    fn polygon(&mut self, corners: &[Point]) {
+        let Some(&first) = corners.first() else {
+            return;
+        };
+        self.pen_down(first);
+        let mut at = first;
+        for &to in corners[1..].iter().chain([&first]) {
+            self.line_by(to.x - at.x, to.y - at.y);
+            at = to;
+        }
+        self.pen_up();
+    }
+
+    /// Fills the shape whose outline is `subpaths`, each closed, with
+    /// `color`. Where they overlap, the nonzero rule decides. Canvases
+    /// which can't fill leave it out.
+    fn filled(&mut self, _subpaths: &[Vec<Point>], _color: &str) {}
+
+    /// Tells that the outline of the latest shape given to `filled` has
+    /// been traced. Only canvases which show the drawing being drawn care.
+    fn end_fill(&mut self) {}
+
+    /// Floods the region around `at` with `color`. `outline` is the
+    /// region as found on the drawing a pixel a step, the same for every
+    /// canvas whatever its resolution.
+    fn flood(&mut self, _at: Point, outline: &[Vec<Point>], color: &str) {
+        self.filled(outline, color);
+    }
+
+    /// Erases everything drawn so far.
+    fn clear(&mut self);
+
+    /// The turtle turns to head towards `angle`, in radians.
+    fn turn(&mut self, _angle: f32) {}
+
+    /// The turtle is shown or hidden. Only canvases which show the drawing
+    /// being drawn care.
+    fn show_turtle(&mut self, _shown: bool) {}
+
+    /// The turtle takes the shape `shape`, as `Robot::setshape` takes it.
+    /// Only canvases which show the drawing being drawn care.
+    fn set_shape(&mut self, _shape: &[Point]) {}
+
+    /// Draws the turtle, whose outline is `outline`, over the finished
+    /// drawing.
+    fn turtle(&mut self, outline: &[Point]) {
+        self.polygon(outline);
+    }
+
+    /// The turtle stops for `seconds`. Only canvases which show the
+    /// drawing being drawn care.
+    fn wait(&mut self, _seconds: f32) {}
+}
+
+/// A call made to a `Canvas`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    PenDown(Point),
+    PenUp,
+    MoveTo(Point),
+    LineBy(f32, f32),
+    SetColor(String),
+    SetLineStyle(LineStyle),
+    SetPenMode(PenMode),
+    Text {
+        at: Point,
+        angle: f32,
+        height: f32,
+        text: String,
+    },
+    Polygon(Vec<Point>),
+    Filled {
+        subpaths: Vec<Vec<Point>>,
+        color: String,
+    },
+    EndFill,
+    Flood {
+        at: Point,
+        outline: Vec<Vec<Point>>,
+        color: String,
+    },
+    Clear,
+    Turn(f32),
+    ShowTurtle(bool),
+    SetShape(Vec<Point>),
+    Wait(f32),
+}
+
+impl Event {
+    /// Makes the same call on `canvas`.
+    pub fn draw(&self, canvas: &mut dyn Canvas) {
+        match self {
+            Event::PenDown(at) => canvas.pen_down(*at),
+            Event::PenUp => canvas.pen_up(),
+            Event::MoveTo(to) => canvas.move_to(*to),
+            Event::LineBy(dx, dy) => canvas.line_by(*dx, *dy),
+            Event::SetColor(color) => canvas.set_color(color),
+            Event::SetLineStyle(style) => canvas.set_line_style(*style),
+            Event::SetPenMode(mode) => canvas.set_pen_mode(*mode),
+            Event::Text {
+                at,
+                angle,
+                height,
+                text,
+            } => canvas.text(*at, *angle, *height, text),
+            Event::Polygon(corners) => canvas.polygon(corners),
+            Event::Filled { subpaths, color } => canvas.filled(subpaths, color),
+            Event::EndFill => canvas.end_fill(),
+            Event::Flood { at, outline, color } => canvas.flood(*at, outline, color),
+            Event::Clear => canvas.clear(),
+            Event::Turn(angle) => canvas.turn(*angle),
+            Event::ShowTurtle(shown) => canvas.show_turtle(*shown),
+            Event::SetShape(shape) => canvas.set_shape(shape),
+            Event::Wait(seconds) => canvas.wait(*seconds),
+        }
+    }
+}
+
+/// A canvas keeping the list of calls made to it, to draw them again on
+/// other canvases.
+#[derive(Default)]
+pub struct Recording {
+    events: Vec<Event>,
+}
+
+impl Recording {
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Puts `event` before the one at `index`, as if it had happened then.
+    pub(crate) fn insert(&mut self, index: usize, event: Event) {
+        self.events.insert(index, event);
+    }
+
+    /// Draws everything recorded on `canvas`.
+    pub fn replay(&self, canvas: &mut dyn Canvas) {
+        for event in &self.events {
+            event.draw(canvas);
+        }
+    }
+}
+
+impl Canvas for Recording {
+    fn pen_down(&mut self, at: Point) {
+        self.events.push(Event::PenDown(at));
+    }
+
+    fn pen_up(&mut self) {
+        self.events.push(Event::PenUp);
+    }
+
+    fn move_to(&mut self, to: Point) {
+        self.events.push(Event::MoveTo(to));
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        self.events.push(Event::LineBy(dx, dy));
+    }
+
+    fn set_color(&mut self, color: &str) {
+        self.events.push(Event::SetColor(color.to_string()));
+    }
+
+    fn set_line_style(&mut self, style: LineStyle) {
+        self.events.push(Event::SetLineStyle(style));
+    }
+
+    fn set_pen_mode(&mut self, mode: PenMode) {
+        self.events.push(Event::SetPenMode(mode));
+    }
+
+    fn text(&mut self, at: Point, angle: f32, height: f32, text: &str) {
+        self.events.push(Event::Text {
+            at,
+            angle,
+            height,
+            text: text.to_string(),
+        });
+    }
+
+    fn polygon(&mut self, corners: &[Point]) {
+        self.events.push(Event::Polygon(corners.to_vec()));
+    }
+
+    fn filled(&mut self, subpaths: &[Vec<Point>], color: &str) {
+        self.events.push(Event::Filled {
+            subpaths: subpaths.to_vec(),
+            color: color.to_string(),
+        });
+    }
+
+    fn end_fill(&mut self) {
+        self.events.push(Event::EndFill);
+    }
+
+    fn flood(&mut self, at: Point, outline: &[Vec<Point>], color: &str) {
+        self.events.push(Event::Flood {
+            at,
+            outline: outline.to_vec(),
+            color: color.to_string(),
+        });
+    }
+
+    fn clear(&mut self) {
+        self.events.push(Event::Clear);
+    }
+
+    fn turn(&mut self, angle: f32) {
+        self.events.push(Event::Turn(angle));
+    }
+
+    fn show_turtle(&mut self, shown: bool) {
+        self.events.push(Event::ShowTurtle(shown));
+    }
+
+    fn set_shape(&mut self, shape: &[Point]) {
+        self.events.push(Event::SetShape(shape.to_vec()));
+    }
+
+    fn wait(&mut self, seconds: f32) {
+        self.events.push(Event::Wait(seconds));
+    }
+}
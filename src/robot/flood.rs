@@ -0,0 +1,127 @@
+use crate::robot::canvas::Point;
+use std::collections::HashMap;
+use tiny_skia::Pixmap;
+
+/// How far apart the channels of two pixels may be for a flood to spread
+/// from one to the other, so that the faint edges of smoothed lines still
+/// stop it.
+const TOLERANCE: u8 = 24;
+
+/// The pixels of an image which a flood starting at one of them covers:
+/// those of its color joined to it side by side.
+pub struct Region {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Region {
+    /// The region around the pixel in column `x` and row `y`, or `None`
+    /// outside of the image.
+    pub fn around(pixmap: &Pixmap, x: f32, y: f32) -> Option<Region> {
+        let (width, height) = (pixmap.width() as usize, pixmap.height() as usize);
+        if !(0. ..width as f32).contains(&x) || !(0. ..height as f32).contains(&y) {
+            return None;
+        }
+        let colors = pixmap.pixels();
+        let seed = colors[y as usize * width + x as usize];
+        let like = |i: usize| {
+            let c = colors[i];
+            [
+                (c.red(), seed.red()),
+                (c.green(), seed.green()),
+                (c.blue(), seed.blue()),
+                (c.alpha(), seed.alpha()),
+            ]
+            .iter()
+            .all(|(a, b)| a.abs_diff(*b) <= TOLERANCE)
+        };
+        let mut pixels = vec![false; width * height];
+        let mut todo = vec![y as usize * width + x as usize];
+        while let Some(i) = todo.pop() {
+            if pixels[i] || !like(i) {
+                continue;
+            }
+            pixels[i] = true;
+            let (x, y) = (i % width, i / width);
+            if x > 0 {
+                todo.push(i - 1);
+            }
+            if x + 1 < width {
+                todo.push(i + 1);
+            }
+            if y > 0 {
+                todo.push(i - width);
+            }
+            if y + 1 < height {
+                todo.push(i + width);
+            }
+        }
+        Some(Region {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn contains(&self, x: isize, y: isize) -> bool {
+        (0..self.width as isize).contains(&x)
+            && (0..self.height as isize).contains(&y)
+            && self.pixels[y as usize * self.width + x as usize]
+    }
+
+    /// Its outline, with pixels `scale` wide: the edges between its pixels
+    /// and the others, joined into closed paths going clockwise around the
+    /// region and counterclockwise around its holes.
+    pub fn outline(&self, scale: f32) -> Vec<Vec<Point>> {
+        // Each edge goes from a corner to the next, the region on its right.
+        let mut edges: HashMap<(isize, isize), Vec<(isize, isize)>> = HashMap::new();
+        for i in (0..self.pixels.len()).filter(|&i| self.pixels[i]) {
+            let (x, y) = ((i % self.width) as isize, (i / self.width) as isize);
+            let sides = [
+                ((x, y), (x + 1, y), (x, y - 1)),
+                ((x + 1, y), (x + 1, y + 1), (x + 1, y)),
+                ((x + 1, y + 1), (x, y + 1), (x, y + 1)),
+                ((x, y + 1), (x, y), (x - 1, y)),
+            ];
+            for (from, to, (nx, ny)) in sides {
+                if !self.contains(nx, ny) {
+                    edges.entry(from).or_default().push(to);
+                }
+            }
+        }
+        let mut starts: Vec<_> = edges.keys().copied().collect();
+        starts.sort_unstable();
+        let mut outline = vec![];
+        for start in starts {
+            while let Some(mut to) = edges.get_mut(&start).and_then(Vec::pop) {
+                let mut corners = vec![start];
+                while to != start {
+                    corners.push(to);
+                    to = match edges.get_mut(&to).and_then(Vec::pop) {
+                        Some(next) => next,
+                        None => break,
+                    };
+                }
+                outline.push(straighten(&corners, scale));
+            }
+        }
+        outline
+    }
+}
+
+/// The points at `corners` scaled by `scale`, leaving out those in the
+/// middle of straight runs.
+fn straighten(corners: &[(isize, isize)], scale: f32) -> Vec<Point> {
+    let n = corners.len();
+    (0..n)
+        .filter(|&i| {
+            let (a, b, c) = (corners[(i + n - 1) % n], corners[i], corners[(i + 1) % n]);
+            (b.0 - a.0) * (c.1 - b.1) != (b.1 - a.1) * (c.0 - b.0)
+        })
+        .map(|i| Point {
+            x: corners[i].0 as f32 * scale,
+            y: corners[i].1 as f32 * scale,
+        })
+        .collect()
+}
@@ -0,0 +1,224 @@
+use crate::error::LogoError;
+use crate::robot;
+use crate::robot::canvas::Canvas;
+use crate::robot::canvas::LineStyle;
+use crate::robot::canvas::PenMode;
+use crate::robot::canvas::Point;
+use crate::robot::raster::PngOptions;
+use crate::robot::raster::Raster;
+use gif::Encoder;
+use gif::Frame;
+use gif::Repeat;
+use std::f32::consts::PI;
+
+/// Size and pace of an animated GIF image.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct GifOptions {
+    /// Width and height in pixels.
+    pub width: u32,
+    /// Lines drawn between frames.
+    pub every: usize,
+    /// Hundredths of a second each frame is shown.
+    pub delay: u16,
+    /// Draws the turtle in each frame while it is shown, not only in the
+    /// last.
+    pub turtle: bool,
+}
+
+impl Default for GifOptions {
+    fn default() -> GifOptions {
+        GifOptions {
+            width: 500,
+            every: 10,
+            delay: 10,
+            turtle: false,
+        }
+    }
+}
+
+fn encode(e: gif::EncodingError) -> LogoError {
+    LogoError::Io(e.to_string())
+}
+
+/// Draws on pixels, taking a frame of an animated GIF image every few
+/// lines and whenever the turtle waits.
+pub struct Gif {
+    raster: Raster,
+    options: GifOptions,
+    encoder: Encoder<Vec<u8>>,
+    /// The last frame taken, kept for as long as the turtle waits.
+    frame: Option<Frame<'static>>,
+    /// Whether anything changed since it was taken.
+    changed: bool,
+    /// Lines drawn since it was taken.
+    lines: usize,
+    /// Where the turtle is.
+    at: Point,
+    angle: f32,
+    shown: bool,
+    shape: Vec<Point>,
+    /// The first error met while encoding.
+    error: Option<LogoError>,
+}
+
+impl Gif {
+    pub fn new(options: GifOptions) -> Result<Gif, LogoError> {
+        let size = u16::try_from(options.width)
+            .map_err(|_| LogoError::Io(format!("can't draw {} pixels wide", options.width)))?;
+        let raster = Raster::new(PngOptions {
+            width: options.width,
+            ..PngOptions::default()
+        })?;
+        let mut encoder = Encoder::new(vec![], size, size, &[]).map_err(encode)?;
+        encoder.set_repeat(Repeat::Infinite).map_err(encode)?;
+        Ok(Gif {
+            raster,
+            options,
+            encoder,
+            frame: None,
+            changed: true,
+            lines: 0,
+            at: Point { x: 0., y: 0. },
+            angle: -PI / 2.,
+            shown: true,
+            shape: robot::shape("triangle").unwrap(),
+            error: None,
+        })
+    }
+
+    /// Writes the last frame taken and takes another.
+    fn take_frame(&mut self) {
+        let turtle = self.options.turtle && self.shown;
+        let outline = turtle.then(|| robot::sprite(self.at, self.angle, &self.shape));
+        self.take_frame_with(outline.as_deref());
+    }
+
+    fn take_frame_with(&mut self, turtle: Option<&[Point]>) {
+        self.write_frame();
+        let pixmap = self.raster.frame(turtle);
+        let size = self.options.width as u16;
+        let mut pixels = pixmap.take();
+        let mut frame = Frame::from_rgba_speed(size, size, &mut pixels, 10);
+        frame.delay = self.options.delay;
+        self.frame = Some(frame);
+        self.changed = false;
+        self.lines = 0;
+    }
+
+    fn write_frame(&mut self) {
+        let Some(frame) = self.frame.take() else {
+            return;
+        };
+        if let Err(e) = self.encoder.write_frame(&frame) {
+            self.error.get_or_insert(encode(e));
+        }
+    }
+
+    /// The image, encoded as a GIF file.
+    pub fn finish(mut self) -> Result<Vec<u8>, LogoError> {
+        if self.changed {
+            self.take_frame();
+        }
+        self.write_frame();
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        self.encoder
+            .into_inner()
+            .map_err(|e| LogoError::Io(e.to_string()))
+    }
+}
+
+impl Canvas for Gif {
+    fn pen_down(&mut self, at: Point) {
+        self.raster.pen_down(at);
+        self.at = at;
+    }
+
+    fn pen_up(&mut self) {
+        self.raster.pen_up();
+    }
+
+    fn move_to(&mut self, to: Point) {
+        self.raster.move_to(to);
+        self.at = to;
+        self.changed |= self.options.turtle;
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        self.raster.line_by(dx, dy);
+        self.at.x += dx;
+        self.at.y += dy;
+        self.changed = true;
+        self.lines += 1;
+        if self.lines >= self.options.every {
+            self.take_frame();
+        }
+    }
+
+    fn set_color(&mut self, color: &str) {
+        self.raster.set_color(color);
+    }
+
+    fn set_line_style(&mut self, style: LineStyle) {
+        self.raster.set_line_style(style);
+    }
+
+    fn set_pen_mode(&mut self, mode: PenMode) {
+        self.raster.set_pen_mode(mode);
+    }
+
+    fn text(&mut self, at: Point, angle: f32, height: f32, text: &str) {
+        self.raster.text(at, angle, height, text);
+        self.changed = true;
+    }
+
+    fn polygon(&mut self, corners: &[Point]) {
+        self.raster.polygon(corners);
+        self.changed = true;
+    }
+
+    fn filled(&mut self, subpaths: &[Vec<Point>], color: &str) {
+        self.raster.filled(subpaths, color);
+        self.changed = true;
+    }
+
+    fn clear(&mut self) {
+        self.raster.clear();
+        self.changed = true;
+    }
+
+    fn turn(&mut self, angle: f32) {
+        self.angle = angle;
+        self.changed |= self.options.turtle;
+    }
+
+    fn show_turtle(&mut self, shown: bool) {
+        self.shown = shown;
+        self.changed |= self.options.turtle;
+    }
+
+    fn set_shape(&mut self, shape: &[Point]) {
+        self.shape = shape.to_vec();
+        self.changed |= self.options.turtle;
+    }
+
+    /// Takes the last frame, with the turtle, unless the last taken has
+    /// it already.
+    fn turtle(&mut self, outline: &[Point]) {
+        if self.changed || !self.options.turtle {
+            self.take_frame_with(Some(outline));
+        }
+    }
+
+    fn wait(&mut self, seconds: f32) {
+        if self.changed {
+            self.take_frame();
+        }
+        if let Some(frame) = &mut self.frame {
+            let delay = (seconds * 100.).round() as u16;
+            frame.delay = frame.delay.saturating_add(delay);
+        }
+    }
+}
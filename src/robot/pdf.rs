@@ -0,0 +1,416 @@
+use crate::robot;
+use crate::robot::canvas::Canvas;
+use crate::robot::canvas::LineCap;
+use crate::robot::canvas::LineJoin;
+use crate::robot::canvas::LineStyle;
+use crate::robot::canvas::PenMode;
+use crate::robot::canvas::Point;
+use ab_glyph::Font;
+use ab_glyph::FontVec;
+use ab_glyph::GlyphId;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::types::BlendMode;
+use pdf_writer::types::CidFontType;
+use pdf_writer::types::FontFlags;
+use pdf_writer::types::LineCapStyle;
+use pdf_writer::types::LineJoinStyle;
+use pdf_writer::types::SystemInfo;
+use pdf_writer::types::UnicodeCmap;
+use pdf_writer::Content;
+use pdf_writer::Filter;
+use pdf_writer::Finish;
+use pdf_writer::Name;
+use pdf_writer::Rect;
+use pdf_writer::Ref;
+use pdf_writer::Str;
+use std::collections::BTreeMap;
+
+/// Side of the square the turtle draws in, as in the SVG `viewBox`.
+const SIDE: f32 = 1000.;
+
+const FONT: Name = Name(b"F1");
+const FONT_NAME: Name = Name(b"LogoSans");
+/// Graphics state of the lines drawn in reverse.
+const REVERSE: Name = Name(b"Reverse");
+const IDENTITY: SystemInfo = SystemInfo {
+    registry: Str(b"Adobe"),
+    ordering: Str(b"Identity"),
+    supplement: 0,
+};
+
+/// Size of the pages of a PDF document, in points of 1/72 inch.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct PdfOptions {
+    pub width: f32,
+    pub height: f32,
+    /// Space left blank around the drawing.
+    pub margin: f32,
+}
+
+/// A4 with half an inch of margin.
+impl Default for PdfOptions {
+    fn default() -> PdfOptions {
+        PdfOptions {
+            width: 595.28,
+            height: 841.89,
+            margin: 36.,
+        }
+    }
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    compress_to_vec_zlib(data, 6)
+}
+
+fn rgb(name: &str) -> Option<[f32; 3]> {
+    let c = robot::color(name)?;
+    Some([c.red, c.green, c.blue].map(|v| v as f32 / 255.))
+}
+
+enum Segment {
+    Move(Point),
+    Line(Point),
+    Close,
+}
+
+/// Draws a drawing on a page.
+struct Page<'a> {
+    content: Content,
+    /// From the turtle's coordinates to the page's.
+    matrix: [f32; 6],
+    font: &'a FontVec,
+    /// Glyphs shown, with the character each is for.
+    glyphs: &'a mut BTreeMap<u16, char>,
+    /// Whether it shows any text.
+    labels: bool,
+    /// Whether it draws any line in reverse.
+    reversed: bool,
+    color: String,
+    style: LineStyle,
+    mode: PenMode,
+    /// The line being drawn while the pen is down.
+    path: Option<Vec<Segment>>,
+    /// Where the pen is.
+    pen: Point,
+}
+
+impl Page<'_> {
+    /// A content stream drawing in the square of the turtle.
+    fn start(matrix: [f32; 6]) -> Content {
+        let mut content = Content::new();
+        content.transform(matrix);
+        content.rect(0., 0., SIDE, SIDE).clip_nonzero().end_path();
+        // As in SVG.
+        content.set_miter_limit(4.);
+        content
+    }
+
+    fn stroke(&mut self, path: &[Segment]) {
+        let [r, g, b] = match self.mode {
+            // An unknown color is no stroke in SVG either.
+            PenMode::Paint => match rgb(&self.color) {
+                Some(rgb) => rgb,
+                None => return,
+            },
+            PenMode::Erase | PenMode::Reverse => [1., 1., 1.],
+        };
+        if !path.iter().any(|s| matches!(s, Segment::Line(_))) {
+            return;
+        }
+        self.content.save_state();
+        if self.mode == PenMode::Reverse {
+            self.content.set_parameters(REVERSE);
+            self.reversed = true;
+        }
+        self.content.set_stroke_rgb(r, g, b);
+        self.content.set_line_width(self.style.width);
+        self.content.set_line_cap(match self.style.cap {
+            LineCap::Butt => LineCapStyle::ButtCap,
+            LineCap::Round => LineCapStyle::RoundCap,
+            LineCap::Square => LineCapStyle::ProjectingSquareCap,
+        });
+        self.content.set_line_join(match self.style.join {
+            LineJoin::Miter => LineJoinStyle::MiterJoin,
+            LineJoin::Round => LineJoinStyle::RoundJoin,
+            LineJoin::Bevel => LineJoinStyle::BevelJoin,
+        });
+        for segment in path {
+            match segment {
+                Segment::Move(p) => self.content.move_to(p.x, p.y),
+                Segment::Line(p) => self.content.line_to(p.x, p.y),
+                Segment::Close => self.content.close_path(),
+            };
+        }
+        self.content.stroke();
+        self.content.restore_state();
+    }
+
+    fn flush_path(&mut self) {
+        if let Some(path) = self.path.take() {
+            self.stroke(&path);
+        }
+    }
+}
+
+impl Canvas for Page<'_> {
+    fn pen_down(&mut self, at: Point) {
+        self.flush_path();
+        self.path = Some(vec![Segment::Move(at)]);
+        self.pen = at;
+    }
+
+    fn pen_up(&mut self) {
+        self.flush_path();
+    }
+
+    fn move_to(&mut self, to: Point) {
+        if let Some(path) = &mut self.path {
+            path.push(Segment::Move(to));
+            self.pen = to;
+        }
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        if let Some(path) = &mut self.path {
+            self.pen.x += dx;
+            self.pen.y += dy;
+            path.push(Segment::Line(self.pen));
+        }
+    }
+
+    fn set_color(&mut self, color: &str) {
+        self.color = color.to_string();
+    }
+
+    fn set_line_style(&mut self, style: LineStyle) {
+        self.style = style;
+    }
+
+    fn set_pen_mode(&mut self, mode: PenMode) {
+        self.mode = mode;
+    }
+
+    fn text(&mut self, at: Point, angle: f32, height: f32, text: &str) {
+        let font = self.font;
+        let em = font.units_per_em().unwrap_or(1000.);
+        let [r, g, b] = rgb(&self.color).unwrap_or([0., 0., 0.]);
+        let (sin, cos) = angle.sin_cos();
+        self.content.set_fill_rgb(r, g, b);
+        self.content.begin_text();
+        self.content.set_font(FONT, height);
+        // The turtle's y grows downwards, the glyphs' upwards.
+        self.content
+            .set_text_matrix([cos, sin, sin, -cos, at.x, at.y]);
+        let mut show = self.content.show_positioned();
+        let mut items = show.items();
+        let mut last = None;
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(last) = last {
+                let kern = font.kern_unscaled(last, id);
+                if kern != 0. {
+                    items.adjust(-kern * 1000. / em);
+                }
+            }
+            items.show(Str(&id.0.to_be_bytes()));
+            self.glyphs.entry(id.0).or_insert(c);
+            self.labels = true;
+            last = Some(id);
+        }
+        items.finish();
+        show.finish();
+        self.content.end_text();
+    }
+
+    fn polygon(&mut self, corners: &[Point]) {
+        let mut outline: Vec<_> = corners.iter().map(|&p| Segment::Line(p)).collect();
+        if let Some(first) = corners.first() {
+            outline[0] = Segment::Move(*first);
+        }
+        outline.push(Segment::Close);
+        self.stroke(&outline);
+    }
+
+    fn filled(&mut self, subpaths: &[Vec<Point>], color: &str) {
+        // SVG fills with black when it doesn't know the color.
+        let [r, g, b] = rgb(color).unwrap_or([0., 0., 0.]);
+        self.content.set_fill_rgb(r, g, b);
+        for subpath in subpaths {
+            for (i, p) in subpath.iter().enumerate() {
+                match i {
+                    0 => self.content.move_to(p.x, p.y),
+                    _ => self.content.line_to(p.x, p.y),
+                };
+            }
+            self.content.close_path();
+        }
+        self.content.fill_nonzero();
+    }
+
+    fn clear(&mut self) {
+        self.content = Page::start(self.matrix);
+    }
+}
+
+/// A PDF document with a page for each drawing.
+pub struct Pdf {
+    pdf: pdf_writer::Pdf,
+    options: PdfOptions,
+    font: FontVec,
+    /// Glyphs shown on any page, to embed their widths.
+    glyphs: BTreeMap<u16, char>,
+    pages: Vec<Ref>,
+    /// Whether any page draws lines in reverse.
+    reversed: bool,
+    /// Last reference given to an object.
+    last: i32,
+}
+
+impl Pdf {
+    const CATALOG: Ref = Ref::new(1);
+    const PAGES: Ref = Ref::new(2);
+    const FONT: Ref = Ref::new(3);
+    const REVERSE: Ref = Ref::new(4);
+
+    pub fn new(options: PdfOptions) -> Pdf {
+        Pdf {
+            pdf: pdf_writer::Pdf::new(),
+            options,
+            font: robot::font(),
+            glyphs: BTreeMap::new(),
+            pages: vec![],
+            reversed: false,
+            last: Pdf::REVERSE.get(),
+        }
+    }
+
+    fn next(&mut self) -> Ref {
+        self.last += 1;
+        Ref::new(self.last)
+    }
+
+    /// Adds a page with what `draw` draws, as large as fits within the
+    /// margins.
+    pub fn page(&mut self, draw: impl FnOnce(&mut dyn Canvas)) {
+        let PdfOptions {
+            width,
+            height,
+            margin,
+        } = self.options;
+        let scale = ((width - 2. * margin).min(height - 2. * margin) / SIDE).max(0.);
+        let x = (width - scale * SIDE) / 2.;
+        let y = (height - scale * SIDE) / 2.;
+        let matrix = [scale, 0., 0., -scale, x, y + scale * SIDE];
+        let mut page = Page {
+            content: Page::start(matrix),
+            matrix,
+            font: &self.font,
+            glyphs: &mut self.glyphs,
+            labels: false,
+            reversed: false,
+            color: "black".to_string(),
+            style: LineStyle::default(),
+            mode: PenMode::Paint,
+            path: None,
+            pen: Point { x: 0., y: 0. },
+        };
+        draw(&mut page);
+        let (labels, reversed) = (page.labels, page.reversed);
+        self.reversed |= reversed;
+        let content = deflate(&page.content.finish());
+
+        let (id, content_id) = (self.next(), self.next());
+        self.pages.push(id);
+        let mut page = self.pdf.page(id);
+        page.media_box(Rect::new(0., 0., width, height))
+            .parent(Pdf::PAGES)
+            .contents(content_id);
+        if labels || reversed {
+            let mut resources = page.resources();
+            if labels {
+                resources.fonts().pair(FONT, Pdf::FONT);
+            }
+            if reversed {
+                resources.ext_g_states().pair(REVERSE, Pdf::REVERSE);
+            }
+        }
+        page.finish();
+        self.pdf
+            .stream(content_id, &content)
+            .filter(Filter::FlateDecode);
+    }
+
+    /// Embeds the font of the labels, as a font whose codes are glyphs.
+    fn embed_font(&mut self) {
+        let (cid, descriptor, file, cmap) = (self.next(), self.next(), self.next(), self.next());
+        let font = &self.font;
+        let em = font.units_per_em().unwrap_or(1000.);
+        let units = |v: f32| v * 1000. / em;
+
+        self.pdf
+            .type0_font(Pdf::FONT)
+            .base_font(FONT_NAME)
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid)
+            .to_unicode(cmap);
+        let mut cid_font = self.pdf.cid_font(cid);
+        cid_font
+            .subtype(CidFontType::Type2)
+            .base_font(FONT_NAME)
+            .system_info(IDENTITY)
+            .font_descriptor(descriptor)
+            .default_width(0.)
+            .cid_to_gid_map_predefined(Name(b"Identity"));
+        let mut widths = cid_font.widths();
+        for &id in self.glyphs.keys() {
+            widths.consecutive(id, [units(font.h_advance_unscaled(GlyphId(id)))]);
+        }
+        widths.finish();
+        cid_font.finish();
+
+        let (ascent, descent) = (
+            units(font.ascent_unscaled()),
+            units(font.descent_unscaled()),
+        );
+        self.pdf
+            .font_descriptor(descriptor)
+            .name(FONT_NAME)
+            .flags(FontFlags::NON_SYMBOLIC)
+            .bbox(Rect::new(0., descent, 1000., ascent))
+            .italic_angle(0.)
+            .ascent(ascent)
+            .descent(descent)
+            .cap_height(ascent)
+            .stem_v(80.)
+            .font_file2(file);
+        self.pdf
+            .stream(file, &deflate(font.as_slice()))
+            .filter(Filter::FlateDecode);
+
+        let mut unicode = UnicodeCmap::new(Name(b"Custom"), IDENTITY);
+        for (&id, &c) in &self.glyphs {
+            unicode.pair(id, c);
+        }
+        self.pdf.stream(cmap, &unicode.finish());
+    }
+
+    /// The document, encoded as a PDF file.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.pdf.catalog(Pdf::CATALOG).pages(Pdf::PAGES);
+        let count = self.pages.len() as i32;
+        self.pdf
+            .pages(Pdf::PAGES)
+            .kids(self.pages.iter().copied())
+            .count(count);
+        if self.reversed {
+            self.pdf
+                .ext_graphics(Pdf::REVERSE)
+                .blend_mode(BlendMode::Difference);
+        }
+        if !self.glyphs.is_empty() {
+            self.embed_font();
+        }
+        self.pdf.finish()
+    }
+}
@@ -0,0 +1,340 @@
+use crate::error::LogoError;
+use crate::robot;
+use crate::robot::canvas::Canvas;
+use crate::robot::canvas::LineCap;
+use crate::robot::canvas::LineJoin;
+use crate::robot::canvas::LineStyle;
+use crate::robot::canvas::PenMode;
+use crate::robot::canvas::Point;
+use crate::robot::flood::Region;
+use ab_glyph::Font;
+use ab_glyph::FontVec;
+use ab_glyph::OutlineCurve;
+use tiny_skia::BlendMode;
+use tiny_skia::Color;
+use tiny_skia::FillRule;
+use tiny_skia::Paint;
+use tiny_skia::Path;
+use tiny_skia::PathBuilder;
+use tiny_skia::Pixmap;
+use tiny_skia::Stroke;
+use tiny_skia::Transform;
+
+/// Side of the square the turtle draws in, as in the SVG `viewBox`.
+const SIDE: f32 = 1000.;
+
+/// Size of a PNG image.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct PngOptions {
+    /// Width and height in pixels.
+    pub width: u32,
+    /// Pixels per inch, kept in the file for printing.
+    pub dpi: u32,
+}
+
+impl Default for PngOptions {
+    fn default() -> PngOptions {
+        PngOptions {
+            width: 1000,
+            dpi: 96,
+        }
+    }
+}
+
+/// The color named as in SVG, or `None` for a name SVG doesn't know.
+fn color(name: &str) -> Option<Color> {
+    let c = robot::color(name)?;
+    Some(Color::from_rgba8(c.red, c.green, c.blue, c.alpha))
+}
+
+/// Strokes `path` on `pixmap` in the color named `name` with `style` in
+/// `mode`.
+fn stroke(
+    pixmap: &mut Pixmap,
+    path: &Path,
+    name: &str,
+    style: LineStyle,
+    mode: PenMode,
+    transform: Transform,
+) {
+    let mut paint = Paint::default();
+    match mode {
+        // An unknown color is no stroke in SVG either.
+        PenMode::Paint => match color(name) {
+            Some(color) => paint.set_color(color),
+            None => return,
+        },
+        PenMode::Erase => paint.set_color(Color::WHITE),
+        PenMode::Reverse => {
+            paint.set_color(Color::WHITE);
+            paint.blend_mode = BlendMode::Difference;
+        }
+    }
+    // Smoothed edges wouldn't come back as they were when a line is drawn
+    // again in reverse.
+    paint.anti_alias = mode != PenMode::Reverse;
+    let stroke = Stroke {
+        width: style.width,
+        line_cap: match style.cap {
+            LineCap::Butt => tiny_skia::LineCap::Butt,
+            LineCap::Round => tiny_skia::LineCap::Round,
+            LineCap::Square => tiny_skia::LineCap::Square,
+        },
+        line_join: match style.join {
+            LineJoin::Miter => tiny_skia::LineJoin::Miter,
+            LineJoin::Round => tiny_skia::LineJoin::Round,
+            LineJoin::Bevel => tiny_skia::LineJoin::Bevel,
+        },
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(path, &paint, &stroke, transform, None);
+}
+
+/// Adds the closed outline through `corners` to `path`.
+fn close(path: &mut PathBuilder, corners: &[Point]) {
+    for (i, p) in corners.iter().enumerate() {
+        match i {
+            0 => path.move_to(p.x, p.y),
+            _ => path.line_to(p.x, p.y),
+        }
+    }
+    path.close();
+}
+
+/// The closed outline through `corners`.
+fn polygon(corners: &[Point]) -> Option<Path> {
+    let mut path = PathBuilder::new();
+    close(&mut path, corners);
+    path.finish()
+}
+
+/// Draws on pixels, the way the SVG canvas draws for a browser.
+pub struct Raster {
+    pixmap: Pixmap,
+    /// From the turtle's coordinates to pixels.
+    transform: Transform,
+    font: FontVec,
+    options: PngOptions,
+    color: String,
+    style: LineStyle,
+    mode: PenMode,
+    /// The line being drawn while the pen is down.
+    path: Option<PathBuilder>,
+    /// Where the pen is.
+    pen: Point,
+}
+
+impl Raster {
+    pub fn new(options: PngOptions) -> Result<Raster, LogoError> {
+        let size = options.width;
+        let mut pixmap = Pixmap::new(size, size)
+            .ok_or_else(|| LogoError::Io(format!("can't draw {size} pixels wide")))?;
+        pixmap.fill(Color::WHITE);
+        let scale = size as f32 / SIDE;
+        Ok(Raster {
+            pixmap,
+            transform: Transform::from_scale(scale, scale),
+            font: robot::font(),
+            options,
+            color: "black".to_string(),
+            style: LineStyle::default(),
+            mode: PenMode::Paint,
+            path: None,
+            pen: Point { x: 0., y: 0. },
+        })
+    }
+
+    fn flush_path(&mut self) {
+        let Some(path) = self.path.take().and_then(PathBuilder::finish) else {
+            return;
+        };
+        let (style, mode) = (self.style, self.mode);
+        stroke(
+            &mut self.pixmap,
+            &path,
+            &self.color,
+            style,
+            mode,
+            self.transform,
+        );
+    }
+
+    /// The region a flood from `at` covers.
+    pub fn region(&self, at: Point) -> Option<Region> {
+        let mut p = tiny_skia::Point { x: at.x, y: at.y };
+        self.transform.map_points(std::slice::from_mut(&mut p));
+        Region::around(&self.pixmap, p.x, p.y)
+    }
+
+    /// The image as it is while the turtle draws, with the turtle whose
+    /// outline is `turtle` if it is given.
+    pub fn frame(&self, turtle: Option<&[Point]>) -> Pixmap {
+        let mut pixmap = self.pixmap.clone();
+        let (style, mode) = (self.style, self.mode);
+        if let Some(path) = self.path.clone().and_then(PathBuilder::finish) {
+            stroke(&mut pixmap, &path, &self.color, style, mode, self.transform);
+        }
+        // The turtle itself is always painted.
+        if let Some(path) = turtle.and_then(polygon) {
+            let mode = PenMode::Paint;
+            stroke(&mut pixmap, &path, &self.color, style, mode, self.transform);
+        }
+        pixmap
+    }
+
+    /// The image, encoded as a PNG file.
+    pub fn png(&self) -> Result<Vec<u8>, LogoError> {
+        let data: Vec<u8> = self
+            .pixmap
+            .pixels()
+            .iter()
+            .flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect();
+        let mut file = vec![];
+        let (width, height) = (self.pixmap.width(), self.pixmap.height());
+        let mut encoder = png::Encoder::new(&mut file, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let ppm = (self.options.dpi as f32 / 0.0254).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: ppm,
+            yppu: ppm,
+            unit: png::Unit::Meter,
+        }));
+        let encode = |e: png::EncodingError| LogoError::Io(e.to_string());
+        let mut writer = encoder.write_header().map_err(encode)?;
+        writer.write_image_data(&data).map_err(encode)?;
+        writer.finish().map_err(encode)?;
+        Ok(file)
+    }
+}
+
+impl Canvas for Raster {
+    fn pen_down(&mut self, at: Point) {
+        self.flush_path();
+        let mut path = PathBuilder::new();
+        path.move_to(at.x, at.y);
+        self.path = Some(path);
+        self.pen = at;
+    }
+
+    fn pen_up(&mut self) {
+        self.flush_path();
+    }
+
+    fn move_to(&mut self, to: Point) {
+        if let Some(path) = &mut self.path {
+            path.move_to(to.x, to.y);
+            self.pen = to;
+        }
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        if let Some(path) = &mut self.path {
+            self.pen.x += dx;
+            self.pen.y += dy;
+            path.line_to(self.pen.x, self.pen.y);
+        }
+    }
+
+    fn set_color(&mut self, color: &str) {
+        self.color = color.to_string();
+    }
+
+    fn set_line_style(&mut self, style: LineStyle) {
+        self.style = style;
+    }
+
+    fn set_pen_mode(&mut self, mode: PenMode) {
+        self.mode = mode;
+    }
+
+    fn text(&mut self, at: Point, angle: f32, height: f32, text: &str) {
+        let font = &self.font;
+        let em = font.units_per_em().unwrap_or(1000.);
+        let mut path = PathBuilder::new();
+        let mut x = 0.;
+        let mut last = None;
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(last) = last {
+                x += font.kern_unscaled(last, id);
+            }
+            let outline = font.outline(id).map_or(vec![], |o| o.curves);
+            let mut end = None;
+            for curve in outline {
+                let (start, to) = match curve {
+                    OutlineCurve::Line(p, q) => (p, q),
+                    OutlineCurve::Quad(p, _, q) => (p, q),
+                    OutlineCurve::Cubic(p, _, _, q) => (p, q),
+                };
+                if end != Some(start) {
+                    path.move_to(x + start.x, start.y);
+                }
+                match curve {
+                    OutlineCurve::Line(_, q) => path.line_to(x + q.x, q.y),
+                    OutlineCurve::Quad(_, a, q) => path.quad_to(x + a.x, a.y, x + q.x, q.y),
+                    OutlineCurve::Cubic(_, a, b, q) => {
+                        path.cubic_to(x + a.x, a.y, x + b.x, b.y, x + q.x, q.y)
+                    }
+                }
+                end = Some(to);
+            }
+            x += font.h_advance_unscaled(id);
+            last = Some(id);
+        }
+        let Some(path) = path.finish() else {
+            return;
+        };
+        let mut paint = Paint::default();
+        paint.set_color(color(&self.color).unwrap_or(Color::BLACK));
+        paint.anti_alias = true;
+        let scale = height / em;
+        // Glyphs are drawn with y growing upwards, from the baseline.
+        let transform = self
+            .transform
+            .pre_translate(at.x, at.y)
+            .pre_rotate(angle.to_degrees())
+            .pre_scale(scale, -scale);
+        self.pixmap
+            .fill_path(&path, &paint, FillRule::Winding, transform, None);
+    }
+
+    fn polygon(&mut self, corners: &[Point]) {
+        if let Some(path) = polygon(corners) {
+            let (style, mode) = (self.style, self.mode);
+            stroke(
+                &mut self.pixmap,
+                &path,
+                &self.color,
+                style,
+                mode,
+                self.transform,
+            );
+        }
+    }
+
+    fn filled(&mut self, subpaths: &[Vec<Point>], name: &str) {
+        let mut path = PathBuilder::new();
+        for subpath in subpaths {
+            close(&mut path, subpath);
+        }
+        let Some(path) = path.finish() else {
+            return;
+        };
+        let mut paint = Paint::default();
+        // SVG fills with black when it doesn't know the color.
+        paint.set_color(color(name).unwrap_or(Color::BLACK));
+        paint.anti_alias = true;
+        self.pixmap
+            .fill_path(&path, &paint, FillRule::Winding, self.transform, None);
+    }
+
+    fn clear(&mut self) {
+        self.pixmap.fill(Color::WHITE);
+    }
+}
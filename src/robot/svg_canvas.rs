@@ -0,0 +1,187 @@
+use crate::robot::canvas::Canvas;
+use crate::robot::canvas::LineCap;
+use crate::robot::canvas::LineJoin;
+use crate::robot::canvas::LineStyle;
+use crate::robot::canvas::PenMode;
+use crate::robot::canvas::Point;
+use std::f32::consts::PI;
+use svg::node;
+use svg::node::element::path::Data;
+use svg::node::element::Path;
+use svg::node::element::Rectangle;
+use svg::Document;
+
+/// The SVG of a line drawn in `color` with `style` in `mode`.
+pub fn path(color: &str, style: LineStyle, mode: PenMode, data: Data) -> Path {
+    let mut path = Path::new()
+        .set("fill", "none")
+        .set("stroke-width", style.width);
+    path = match mode {
+        PenMode::Paint => path.set("stroke", color),
+        PenMode::Erase => path.set("stroke", "white"),
+        // White over white is black; it needs the backdrop.
+        PenMode::Reverse => path
+            .set("stroke", "white")
+            .set("style", "mix-blend-mode: difference"),
+    };
+    // SVG's own defaults are left out.
+    if style.cap != LineCap::Butt {
+        path = path.set("stroke-linecap", style.cap.name());
+    }
+    if style.join != LineJoin::Miter {
+        path = path.set("stroke-linejoin", style.join.name());
+    }
+    path.set("d", data)
+}
+
+/// The SVG of a label.
+pub fn label(at: Point, angle: f32, height: f32, color: &str, text: &str) -> node::element::Text {
+    node::element::Text::new()
+        .set("x", at.x)
+        .set("y", at.y)
+        .set("fill", color)
+        .set(
+            "transform",
+            format!("rotate({} {},{})", 180.0 * angle / PI, at.x, at.y),
+        )
+        .set("style", format!("font: {height}px sans-serif;"))
+        .add(node::Text::new(text))
+}
+
+/// `data` followed by the closed outline through `corners`.
+fn close(mut data: Data, corners: &[Point]) -> Data {
+    for (i, p) in corners.iter().enumerate() {
+        data = match i {
+            0 => data.move_to((p.x, p.y)),
+            _ => data.line_to((p.x, p.y)),
+        };
+    }
+    data.close()
+}
+
+/// The SVG of the closed outline through `corners`.
+pub fn polygon(corners: &[Point], color: &str, style: LineStyle, mode: PenMode) -> Path {
+    path(color, style, mode, close(Data::new(), corners))
+}
+
+/// A white background for lines drawn in reverse to invert.
+pub fn backdrop() -> Rectangle {
+    Rectangle::new()
+        .set("width", 1000)
+        .set("height", 1000)
+        .set("fill", "white")
+}
+
+/// The SVG of a shape filled with `color`.
+pub fn filled(subpaths: &[Vec<Point>], color: &str) -> Path {
+    let data = subpaths.iter().fold(Data::new(), |d, s| close(d, s));
+    Path::new().set("fill", color).set("d", data)
+}
+
+/// Something drawn on an `SvgCanvas`.
+enum Drawn {
+    Path(Path),
+    Label(node::element::Text),
+}
+
+/// Draws as an SVG document, one path for each line.
+pub struct SvgCanvas {
+    drawn: Vec<Drawn>,
+    /// Whether any of them is drawn in reverse.
+    reversed: bool,
+    color: String,
+    style: LineStyle,
+    mode: PenMode,
+    /// The line being drawn while the pen is down.
+    data: Option<Data>,
+}
+
+impl SvgCanvas {
+    pub fn new() -> SvgCanvas {
+        SvgCanvas {
+            drawn: vec![],
+            reversed: false,
+            color: "black".to_string(),
+            style: LineStyle::default(),
+            mode: PenMode::Paint,
+            data: None,
+        }
+    }
+
+    /// Adds a line, drawn in the pen mode.
+    fn stroke(&mut self, data: Data) {
+        self.reversed |= self.mode == PenMode::Reverse;
+        let path = path(&self.color, self.style, self.mode, data);
+        self.drawn.push(Drawn::Path(path));
+    }
+
+    fn flush_path(&mut self) {
+        if let Some(data) = self.data.take() {
+            self.stroke(data);
+        }
+    }
+
+    /// What has been drawn, leaving out the line not yet ended.
+    pub fn document(self) -> Document {
+        let mut document = Document::new();
+        if self.reversed {
+            document = document.add(backdrop());
+        }
+        for drawn in self.drawn {
+            document = match drawn {
+                Drawn::Path(path) => document.add(path),
+                Drawn::Label(text) => document.add(text),
+            };
+        }
+        document.set("viewBox", (0, 0, 1000, 1000))
+    }
+}
+
+impl Canvas for SvgCanvas {
+    fn pen_down(&mut self, at: Point) {
+        self.flush_path();
+        self.data = Some(Data::new().move_to((at.x, at.y)));
+    }
+
+    fn pen_up(&mut self) {
+        self.flush_path();
+    }
+
+    fn move_to(&mut self, to: Point) {
+        self.data = self.data.take().map(|d| d.move_to((to.x, to.y)));
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        self.data = self.data.take().map(|d| d.line_by((dx, dy)));
+    }
+
+    fn set_color(&mut self, color: &str) {
+        self.color = color.to_string();
+    }
+
+    fn set_line_style(&mut self, style: LineStyle) {
+        self.style = style;
+    }
+
+    fn set_pen_mode(&mut self, mode: PenMode) {
+        self.mode = mode;
+    }
+
+    fn text(&mut self, at: Point, angle: f32, height: f32, text: &str) {
+        let text = label(at, angle, height, &self.color, text);
+        self.drawn.push(Drawn::Label(text));
+    }
+
+    fn polygon(&mut self, corners: &[Point]) {
+        self.stroke(close(Data::new(), corners));
+    }
+
+    fn filled(&mut self, subpaths: &[Vec<Point>], color: &str) {
+        self.drawn.push(Drawn::Path(filled(subpaths, color)));
+    }
+
+    fn clear(&mut self) {
+        self.drawn.clear();
+        self.reversed = false;
+    }
+}
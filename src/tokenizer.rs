@@ -1,12 +1,237 @@
+use crate::error::LogoError;
 use santiago::lexer::LexerRules;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+// A word character: anything but a delimiter, a backslash escape or a
+// `|...|` segment. Infix operators delimit words unless they are quoted.
+macro_rules! word {
+    ($delim:literal) => {
+        concat!(r"(?:~*(?:\\.|\|[^|]*\||[^\s\[\]();|\\~", $delim, r"]))+")
+    };
+}
 
 pub fn lexer_rules() -> LexerRules {
     santiago::lexer_rules!(
-        "DEFAULT" | "STRING" = pattern "\"[a-zA-Z_]+";
-        "DEFAULT" | "LABEL" = pattern r":[a-zA-Z_]+";
-        "DEFAULT" | "PROC" = pattern r"[a-zA-Z_]+";
-        "DEFAULT" | "NUM" = pattern r"-?[0-9]+(\.[0-9])?";
-        "DEFAULT" | "SPEC" = pattern r"[\[\]+\-*/<>()]";
+        "DEFAULT" | "STRING" = pattern concat!("\"(?:", word!(""), ")?");
+        "DEFAULT" | "LABEL" = pattern concat!(":", word!(r"+\-*/=<>"));
+        "DEFAULT" | "NUM" = pattern r"(?:[0-9]+(?:\.[0-9]*)?|\.[0-9]+)(?:[eE][+\-]?[0-9]+)?";
+        "DEFAULT" | "PROC" = pattern concat!(word!(r"+\-*/=<>"), "|~+");
+        "DEFAULT" | "SPEC" = pattern r"[\[\]+\-*/=<>()]";
+        "DEFAULT" | "COMMENT" = pattern r";[^\n]*";
+        "DEFAULT" | "CONT" = pattern r"~[ \t]*\r?\n" => |lexer| lexer.skip();
         "DEFAULT" | "WS" = pattern r"\s" => |lexer| lexer.skip();
     )
 }
+
+/// The number `text` reads as, if the lexer would read it as a number,
+/// maybe negated. Words such as `inf` or `nan` are not numbers.
+pub fn read_number(text: &str) -> Option<f32> {
+    let digits = |s: &str| s.bytes().take_while(u8::is_ascii_digit).count();
+    let unsigned = text.strip_prefix('-').unwrap_or(text);
+    let whole = digits(unsigned);
+    let mut rest = &unsigned[whole..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let n = digits(fraction);
+        if whole == 0 && n == 0 {
+            return None;
+        }
+        rest = &fraction[n..];
+    } else if whole == 0 {
+        return None;
+    }
+    if let Some(exp) = rest.strip_prefix(['e', 'E']) {
+        let exp = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+        if digits(exp) == 0 {
+            return None;
+        }
+        rest = &exp[digits(exp)..];
+    }
+    rest.is_empty().then(|| text.parse().ok())?
+}
+
+/// Text of a word with escapes and vertical bars resolved.
+fn unescape(raw: &str) -> String {
+    let mut text = String::new();
+    let mut bars = false;
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' => bars = !bars,
+            '\\' if !bars => text.extend(chars.next()),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+/// Source of a word with text `text`: characters which would end it are
+/// escaped. `ops` tells whether infix operators end it too.
+pub fn escape(text: &str, ops: bool) -> String {
+    let mut raw = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || "[]();|\\~".contains(c) || ops && "+-*/=<>".contains(c) {
+            raw.push('\\');
+        }
+        raw.push(c);
+    }
+    raw
+}
+
+/// Line and column (both counted from 1) in the source.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Pos {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Source range of a token, `end` is just past its last character.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Debug)]
+pub enum Kind {
+    /// Quoted word, `"abc`.
+    Word,
+    /// Variable reference, `:abc`.
+    Var,
+    /// Procedure name or bare word.
+    Name,
+    Num,
+    /// Brackets, parentheses and infix operators.
+    Spec,
+    /// Minus sign negating the value right after it.
+    Neg,
+    /// `; ...` up to the end of the line.
+    Comment,
+}
+
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct Token {
+    pub text: String,
+    pub kind: Kind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn pos(&self) -> Pos {
+        self.span.start
+    }
+
+    /// Whether it is the given bracket or operator, not a word spelled so.
+    pub fn is(&self, spec: &str) -> bool {
+        self.kind == Kind::Spec && self.text == spec
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.text)
+    }
+}
+
+impl AsRef<str> for Token {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+fn kind(name: &str) -> Kind {
+    match name {
+        "STRING" => Kind::Word,
+        "LABEL" => Kind::Var,
+        "PROC" => Kind::Name,
+        "NUM" => Kind::Num,
+        "COMMENT" => Kind::Comment,
+        _ => Kind::Spec,
+    }
+}
+
+/// UCBLogo's rule for `-`: it is unary when the next token is glued to it
+/// and it follows a space, an opening bracket or another operator; otherwise
+/// it subtracts. A unary minus glued to a number becomes part of it.
+fn unary_minus(tokens: Vec<Token>) -> Vec<Token> {
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(mut tok) = iter.next() {
+        let operand_expected = match out.last() {
+            None => true,
+            Some(prev) => {
+                prev.span.end != tok.span.start
+                    || (prev.kind == Kind::Spec && !prev.is(")") && !prev.is("]"))
+                    || prev.kind == Kind::Neg
+            }
+        };
+        let glued = iter
+            .peek()
+            .filter(|next| next.span.start == tok.span.end)
+            .map(|next| next.kind);
+        match glued {
+            Some(Kind::Num) if tok.is("-") && operand_expected => {
+                let num = iter.next().unwrap();
+                tok.text.push_str(&num.text);
+                tok.kind = Kind::Num;
+                tok.span.end = num.span.end;
+            }
+            Some(_) if tok.is("-") && operand_expected => tok.kind = Kind::Neg,
+            _ => (),
+        }
+        out.push(tok);
+    }
+    out
+}
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>, LogoError> {
+    Ok(tokenize_with_comments(src)?.0)
+}
+
+/// Tokens of `src` and, separately, its comments.
+pub fn tokenize_with_comments(src: &str) -> Result<(Vec<Token>, Vec<Token>), LogoError> {
+    let lexemes = santiago::lexer::lex(&lexer_rules(), src).map_err(|e| {
+        let pos = Pos {
+            line: e.position.line,
+            column: e.position.column,
+        };
+        let c = src[e.byte_index..].chars().next().unwrap_or_default();
+        LogoError::Lex(format!("unexpected character {c:?}")).at(pos)
+    })?;
+    let tokens = lexemes
+        .iter()
+        .map(|l| {
+            let start = Pos {
+                line: l.position.line,
+                column: l.position.column,
+            };
+            let mut end = l.position.clone();
+            end.consume(&l.raw);
+            let kind = kind(&l.kind);
+            let text = match kind {
+                Kind::Spec | Kind::Num | Kind::Comment => l.raw.clone(),
+                _ => unescape(&l.raw),
+            };
+            Token {
+                text,
+                kind,
+                span: Span {
+                    start,
+                    end: Pos {
+                        line: end.line,
+                        column: end.column,
+                    },
+                },
+            }
+        })
+        .collect::<Vec<Token>>();
+    let (comments, tokens) = tokens.into_iter().partition(|t| t.kind == Kind::Comment);
+    Ok((unary_minus(tokens), comments))
+}
@@ -1,6 +1,7 @@
 pub struct Unsee<'a, Item> {
     stack: Vec<Item>,
     iter: Box<dyn Iterator<Item = Item> + 'a>,
+    taken: usize,
 }
 
 impl<'a, Item> Unsee<'a, Item> {
@@ -8,9 +9,15 @@ impl<'a, Item> Unsee<'a, Item> {
         Unsee {
             stack: vec![],
             iter: Box::new(iter),
+            taken: 0,
         }
     }
 
+    /// Number of items taken from the wrapped iterator and not given back.
+    pub fn consumed(&self) -> usize {
+        self.taken - self.stack.len()
+    }
+
     pub fn unsee(&mut self, s: Item) {
         self.stack.push(s);
     }
@@ -21,7 +28,11 @@ impl<'a, Item> Iterator for Unsee<'a, Item> {
 
     fn next(&mut self) -> Option<Item> {
         match self.stack.pop() {
-            None => self.iter.next(),
+            None => {
+                let s = self.iter.next()?;
+                self.taken += 1;
+                Some(s)
+            }
             Some(s) => Some(s),
         }
     }
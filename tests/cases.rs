@@ -49,3 +49,560 @@ fn case3() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn case4() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case4.logo");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("10\n1\n1\n"));
+
+    Ok(())
+}
+
+#[test]
+fn case5() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case5.logo");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("/ doesn't like 0 as input"));
+
+    Ok(())
+}
+
+#[test]
+fn case6() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case6.logo");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("* doesn't like \"five as input"));
+
+    Ok(())
+}
+
+#[test]
+fn case7() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case7.logo");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "fd doesn't like \"abc as input\n  at progs/case7.logo:2:3\n  in fern called from tree\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn case8() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case8.logo");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "I don't know how to sqare; did you mean square?\n  at progs/case8.logo:4:1",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn case9() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case9.logo");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Zażółć\nhello.world\ntwo words\na b;c\n[ red2 blue \"x+y ]\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn case10() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case10.logo");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "0.25\n15\n0.5\n4\n4\n-10\n-6\n[ fd -10 :a -b ]\n3\n",
+        ))
+        .stderr(predicate::str::contains("You don't say what to do with -1"));
+
+    Ok(())
+}
+
+#[test]
+fn case11() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case11.logo");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("10\n6\n"))
+        .stderr(predicate::str::contains(
+            "FD doesn't like \"x as input\n  at progs/case11.logo:9:3\n  in Broken",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn case12() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("progs/case12.logo");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("6\n6\n5\n"))
+        .stderr(predicate::str::contains(
+            "You don't say what to do with 3\n  at progs/case12.logo:5:22\n  in g",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn case13() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-O").arg("--dump-tree").arg("progs/case13.logo");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("70\n20\n6\nalways\n0\n"))
+        .stderr(predicate::str::contains(
+            "7:1: print 70\n8:1: make \"size 30\n9:1: print ((:size * 2) / 3)\n10:1: print 6\n11:1: nothing\n12:1: if 1 [ print \"always ]\n",
+        ))
+        .stderr(predicate::str::contains("13:1: print (double (random 1))\n"));
+
+    Ok(())
+}
+
+#[test]
+fn case14() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("check").arg("progs/case14.logo");
+    cmd.assert().failure().stdout(concat!(
+        "progs/case14.logo:1:1: sq sometimes outputs a value and sometimes doesn't\n",
+        "progs/case14.logo:3:6: size has no value\n",
+        "progs/case14.logo:7:3: code after output is never run\n",
+        "progs/case14.logo:14:7: You don't say what to do with 20\n",
+        "progs/case14.logo:17:1: You don't say what to do with sq 3\n",
+        "progs/case14.logo:17:6: You don't say what to do with 4\n",
+        "progs/case14.logo:18:7: I don't know how to frob\n",
+        "progs/case14.logo:19:7: nope has no value\n",
+        "progs/case14.logo:21:7: I don't know how to sum; did you mean run?\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn case25() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("check").arg("progs/case25.logo");
+    cmd.assert().failure().stdout(concat!(
+        "progs/case25.logo:2:1: You don't say what to do with pensize\n",
+        "progs/case25.logo:4:1: You don't say what to do with shown?\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn check_clean() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("check").arg("progs/tree.logo");
+    cmd.assert().success().stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn case15() -> Result<(), Box<dyn std::error::Error>> {
+    let formatted = concat!(
+        "; Draws a row of stars.\n",
+        "to Star :Size ; one star\n",
+        "  repeat 5 [\n",
+        "    forward :size\n",
+        "    right 144\n",
+        "  ]\n",
+        "  if :size < 5 [ stop ]\n",
+        "end\n",
+        "to row :n\n",
+        "  repeat :n [\n",
+        "    Star 20\n",
+        "    penup\n",
+        "    forward 30\n",
+        "    pendown ; next\n",
+        "  ]\n",
+        "end\n",
+        "clearscreen\n",
+        "penup\n",
+        "back 100\n",
+        "pendown\n",
+        "row 5\n",
+        "setpencolor \"blue\n",
+        "Star -(10 - 15) * 2\n",
+    );
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("fmt").arg("progs/case15.logo");
+    cmd.assert().success().stdout(formatted);
+
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("fmt").arg("--check").arg("progs/case15.logo");
+    cmd.assert().failure().stdout("progs/case15.logo\n");
+
+    Ok(())
+}
+
+#[test]
+fn fmt_check_formatted() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("fmt").arg("--check").arg("progs/case12.logo");
+    cmd.assert().success().stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn repl() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir();
+    let pict = dir.join("logo-repl.svg");
+    let mut cmd = assert_cmd::Command::cargo_bin("logo")?;
+
+    cmd.env("HOME", &dir).write_stdin(format!(
+        "print 3 * 4\nto sq :x\n  output :x * :x\nEND\nsq 5\n[a b]\nfrob\nfd 10 savepict \"{}\nbye\nprint 1\n",
+        pict.display()
+    ));
+    cmd.assert()
+        .success()
+        .stdout("12\n25\n[ a b ]\n")
+        .stderr("I don't know how to frob\n  at input:1:1\n");
+    assert!(std::fs::read_to_string(pict)?.contains("<path d=\"M500,500 l"));
+
+    Ok(())
+}
+
+#[test]
+fn case17() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-hidden.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).arg("progs/case17.logo");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("1\n0\n"));
+    // Only the line, without the turtle.
+    let data = std::fs::read_to_string(svg)?;
+    assert_eq!(data.matches("<path").count(), 1);
+    assert!(!data.contains(" z\""));
+
+    Ok(())
+}
+
+#[test]
+fn case18() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-filled.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).arg("progs/case18.logo");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("red\n"));
+    let data = std::fs::read_to_string(svg)?;
+    // The fill, with a part for each square, goes under their lines.
+    let fill = data.find(r#"fill="gold""#).unwrap();
+    assert_eq!(data[..fill].matches(" z M").count(), 1);
+    assert!(data[fill..].contains(r#"stroke="black""#));
+    assert!(data.contains(r#"fill="green""#));
+
+    Ok(())
+}
+
+#[test]
+fn case19() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-flood.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).arg("progs/case19.logo");
+    cmd.assert().success();
+    let data = std::fs::read_to_string(svg)?;
+    // The inside of the square, up to its lines.
+    assert!(data.contains(r#"d="M501,401 L599,401 L599,499 L501,499 z" fill="red""#));
+    // The screen around it, with a hole where it is.
+    let around = data.find(r#"fill="lightgray""#).unwrap();
+    assert_eq!(
+        data[..around]
+            .matches("M0,0 L1000,0 L1000,1000 L0,1000 z M")
+            .count(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn flood_thumbnail() -> Result<(), Box<dyn std::error::Error>> {
+    let png = std::env::temp_dir().join("logo-flood.png");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&png).args(["--width", "100"]);
+    cmd.arg("progs/case19.logo");
+    cmd.assert().success();
+    let mut reader = png::Decoder::new(std::fs::File::open(png)?).read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut data)?;
+    let pixel = |x: usize, y: usize| &data[4 * (100 * y + x)..][..3];
+    // The regions found on the drawing as large as the SVG, not on the
+    // thumbnail where its lines are too thin to stop a flood.
+    assert_eq!(pixel(55, 45), [255, 0, 0]);
+    assert_eq!(pixel(5, 5), [211, 211, 211]);
+
+    Ok(())
+}
+
+#[test]
+fn case22() -> Result<(), Box<dyn std::error::Error>> {
+    let png = std::env::temp_dir().join("logo-label.png");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.env("LOGO_FONT", "no-such-font.ttf");
+    cmd.arg("-o").arg(&png).args(["--width", "100"]);
+    cmd.arg("progs/case22.logo");
+    cmd.assert().success();
+    let mut reader = png::Decoder::new(std::fs::File::open(png)?).read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut data)?;
+    // Without the font asked for the label is written in the bundled one.
+    assert!(data.chunks(4).any(|p| p[..3] != [255, 255, 255]));
+
+    Ok(())
+}
+
+#[test]
+fn case20() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-pensize.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).arg("progs/case20.logo");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[ 5 5 ]\n"));
+    let data = std::fs::read_to_string(svg)?;
+    let v = data
+        .lines()
+        .find(|l| l.contains(r#"d="M500,500 l"#))
+        .unwrap();
+    assert!(v.ends_with(r#"stroke-linecap="round" stroke-linejoin="round" stroke-width="5"/>"#));
+
+    Ok(())
+}
+
+#[test]
+fn case21() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-penmode.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).arg("progs/case21.logo");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("paint\nreverse\npaint\n"));
+    let data = std::fs::read_to_string(svg)?;
+    // What is drawn in reverse is inverted over a white background.
+    assert!(data.contains(r#"<rect fill="white" height="1000" width="1000"/>"#));
+    assert_eq!(data.matches("mix-blend-mode: difference").count(), 1);
+    // Rubbing out is drawing in white.
+    let line = data.lines().rev().find(|l| l.contains(r#"d="M500,500 l"#));
+    assert!(line
+        .unwrap()
+        .ends_with(r#"stroke="white" stroke-width="1"/>"#));
+
+    Ok(())
+}
+
+#[test]
+fn png_output() -> Result<(), Box<dyn std::error::Error>> {
+    let png = std::env::temp_dir().join("logo-star.png");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o")
+        .arg(&png)
+        .args(["--width", "200", "--dpi", "254"]);
+    cmd.arg("progs/star.logo");
+    cmd.assert().success();
+    let data = std::fs::read(png)?;
+    assert!(data.starts_with(b"\x89PNG\r\n\x1a\n"));
+    // IHDR: 200 by 200 pixels.
+    assert_eq!(data[16..24], [0, 0, 0, 200, 0, 0, 0, 200]);
+    // pHYs: 10000 pixels per metre.
+    let phys = data.windows(4).position(|w| w == b"pHYs").unwrap();
+    assert_eq!(data[phys + 4..phys + 13], [0, 0, 39, 16, 0, 0, 39, 16, 1]);
+
+    Ok(())
+}
+
+#[test]
+fn gif_output() -> Result<(), Box<dyn std::error::Error>> {
+    let gif = std::env::temp_dir().join("logo-star.gif");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o")
+        .arg(&gif)
+        .args(["--width", "120", "--every", "1", "--turtle"]);
+    cmd.arg("progs/star.logo");
+    cmd.assert().success();
+    let data = std::fs::read(gif)?;
+    assert!(data.starts_with(b"GIF89a"));
+    // 120 by 120 pixels, little-endian.
+    assert_eq!(data[6..10], [120, 0, 120, 0]);
+    assert!(data.windows(11).any(|w| w == b"NETSCAPE2.0"));
+
+    Ok(())
+}
+
+#[test]
+fn case23() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-wait.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).arg("--animate");
+    cmd.arg("progs/case23.logo");
+    cmd.assert().success();
+    let data = std::fs::read_to_string(&svg)?;
+    // Waiting a negative time doesn't go back in time.
+    assert!(data.contains(r#"begin="0s" dur="0.5s""#));
+    assert!(!data.contains(r#"begin="-"#));
+
+    for speed in ["0", "-1", "inf", "NaN"] {
+        let mut cmd = Command::cargo_bin("logo")?;
+        cmd.arg("-o").arg(&svg).args(["--speed", speed]);
+        cmd.arg("progs/case23.logo");
+        cmd.assert().failure().code(2);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn case24() -> Result<(), Box<dyn std::error::Error>> {
+    for engine in [None, Some("--vm")] {
+        let mut cmd = Command::cargo_bin("logo")?;
+
+        cmd.args(engine).arg("progs/case24.logo");
+        cmd.assert()
+            .success()
+            .stdout("File: progs/case24.logo\n3\n6\n");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn case26() -> Result<(), Box<dyn std::error::Error>> {
+    // Statements after a definition are parsed and called anew.
+    for engine in [None, Some("--vm")] {
+        let mut cmd = Command::cargo_bin("logo")?;
+
+        cmd.args(engine).arg("progs/case26.logo");
+        cmd.assert()
+            .success()
+            .stdout("File: progs/case26.logo\n1\n20\n3\n5\n70\n70\n7\n");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn pdf_pages() -> Result<(), Box<dyn std::error::Error>> {
+    let pdf = std::env::temp_dir().join("logo-pages.pdf");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o")
+        .arg(&pdf)
+        .args(["--page", "letter", "--margin", "10"]);
+    cmd.args(["progs/star.logo", "progs/logo.logo"]);
+    cmd.assert().success().stdout(predicate::str::starts_with(
+        "File: progs/star.logo\nFile: progs/logo.logo\n",
+    ));
+    let data = String::from_utf8_lossy(&std::fs::read(pdf)?).into_owned();
+    assert!(data.starts_with("%PDF-"));
+    assert!(data.contains("/Count 2"));
+    assert_eq!(data.matches("/MediaBox [0 0 612 792]").count(), 2);
+    assert!(data.contains("/Subtype /CIDFontType2"));
+
+    let mut cmd = Command::cargo_bin("logo")?;
+    cmd.args(["progs/star.logo", "progs/logo.logo"]);
+    cmd.assert().failure().code(2);
+
+    Ok(())
+}
+
+#[test]
+fn pdf_label() -> Result<(), Box<dyn std::error::Error>> {
+    let pdf = std::env::temp_dir().join("logo-label.pdf");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.env("LOGO_FONT", "no-such-font.ttf");
+    cmd.arg("-o").arg(&pdf).arg("progs/case22.logo");
+    cmd.assert().success();
+    let data = String::from_utf8_lossy(&std::fs::read(pdf)?).into_owned();
+    assert!(data.contains("/FontFile2"));
+    assert!(data.contains("/F1"));
+
+    Ok(())
+}
+
+#[test]
+fn animated_svg() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-animated.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).args(["--speed", "100"]);
+    cmd.arg("progs/case16.logo");
+    cmd.assert().success();
+    let data = std::fs::read_to_string(svg)?;
+    // fd 100 takes a second and the turtle waits for another, all while
+    // tracing the one line.
+    assert!(data.contains(r#"begin="0s" dur="2.5s""#));
+    assert!(data.contains(r#"keyTimes="0;0.4;0.8;1" values="150;50;50;0""#));
+    assert!(data.contains(r#"attributeName="stroke-dashoffset""#));
+    assert!(
+        data.contains(r#"<set attributeName="visibility" begin="2s" fill="freeze" to="visible"/>"#)
+    );
+    // cs hides what was drawn before.
+    assert_eq!(data.matches(r#"to="hidden""#).count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn animated_fill() -> Result<(), Box<dyn std::error::Error>> {
+    let svg = std::env::temp_dir().join("logo-animated-fill.svg");
+    let mut cmd = Command::cargo_bin("logo")?;
+
+    cmd.arg("-o").arg(&svg).arg("--animate");
+    cmd.arg("progs/case18.logo");
+    cmd.assert().success();
+    let data = std::fs::read_to_string(svg)?;
+    // Each fill shows once its outline has been traced.
+    assert!(data.contains(r#"begin="2.25s" dur="1s""#));
+    assert!(data
+        .contains(r#"<set attributeName="visibility" begin="3.25s" fill="freeze" to="visible"/>"#));
+    assert!(data.contains(r#"begin="3.25s" dur="2.5s""#));
+    assert!(data
+        .contains(r#"<set attributeName="visibility" begin="5.75s" fill="freeze" to="visible"/>"#));
+
+    Ok(())
+}
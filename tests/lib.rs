@@ -0,0 +1,345 @@
+use logo::Canvas;
+use logo::Config;
+use logo::Context;
+use logo::Event;
+use logo::Interpreter;
+use logo::LineCap;
+use logo::LineJoin;
+use logo::LineStyle;
+use logo::LogoError;
+use logo::PngOptions;
+use logo::Point;
+use logo::Primitive;
+use logo::Value;
+use std::io::Cursor;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+/// Output shared with the interpreter, to read what it printed.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn render_in_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let mut logo = Interpreter::new(Config::default());
+
+    logo.run(&std::fs::read_to_string("progs/star.logo")?)?;
+    // Rendering only reads the drawing, the same every time.
+    let shared = &logo;
+    assert_eq!(
+        shared.render_svg(),
+        std::fs::read_to_string("progs/star.svg")?
+    );
+    assert_eq!(shared.render_svg(), shared.drawing().to_string());
+
+    Ok(())
+}
+
+#[test]
+fn eval_and_io() -> Result<(), Box<dyn std::error::Error>> {
+    let out = Output::default();
+    let mut logo = Interpreter::new(Config::default());
+    logo.set_stdout(out.clone());
+    logo.set_stdin(Cursor::new("fd 10\nhello world\n"));
+
+    logo.run("to sq :x\n  output :x * :x\nend")?;
+    assert_eq!(logo.eval("sq 5")?, Value::Num(25.));
+    assert_eq!(logo.eval("print sq 3")?, Value::Void);
+    assert_eq!(logo.eval("print readlist print readword")?, Value::Void);
+    assert_eq!(
+        String::from_utf8(out.0.lock().unwrap().clone())?,
+        "9\n[ fd 10 ]\nhello world\n"
+    );
+    assert_eq!(
+        logo.eval("sq 2 sq 3").unwrap_err().to_string(),
+        "You don't say what to do with 4 at 1:1"
+    );
+    assert!(logo.run("sq 2").is_err());
+    assert_eq!(logo.eval("[ a [ b c ] d ]")?.to_string(), "[ a [ b c ] d ]");
+    assert_eq!(
+        logo.run("print [ a [ b ]").unwrap_err().to_string(),
+        "unexpected unmatched [ at 1:7"
+    );
+    assert!(logo.names().any(|n| n == "sq"));
+
+    Ok(())
+}
+
+#[test]
+fn run_on_another_thread() -> Result<(), Box<dyn std::error::Error>> {
+    let out = Output::default();
+    let mut logo = Interpreter::new(Config::default());
+    logo.set_stdout(out.clone());
+    logo.register(Double);
+
+    logo.run("to sq :x\n  output :x * :x\nend")?;
+    let mut logo = thread::spawn(move || logo.run("print sq dbl 3").map(|()| logo))
+        .join()
+        .unwrap()?;
+    assert_eq!(logo.eval("sq 2")?, Value::Num(4.));
+    assert_eq!(String::from_utf8(out.0.lock().unwrap().clone())?, "36\n");
+
+    Ok(())
+}
+
+#[test]
+fn number_words() -> Result<(), Box<dyn std::error::Error>> {
+    let mut logo = Interpreter::new(Config::default());
+
+    assert_eq!(logo.eval("\"1.5e1 - \"-1")?, Value::Num(16.));
+    // Rust reads these as numbers, Logo doesn't.
+    for word in ["inf", "NaN", "infinity", "+5", "1e"] {
+        assert_eq!(
+            logo.run(&format!("fd \"{word}")).unwrap_err().to_string(),
+            format!("fd doesn't like \"{word} as input at 1:1")
+        );
+    }
+    assert!(logo.run("setshape [0 10 inf 0 -5 0]").is_err());
+
+    Ok(())
+}
+
+struct Double;
+
+impl Primitive for Double {
+    fn names(&self) -> &[&str] {
+        &["double", "dbl"]
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(&self, _: &mut Context, inputs: Vec<Value>) -> Result<Value, LogoError> {
+        match inputs[0].as_num() {
+            Some(n) => Ok(Value::Num(2. * n)),
+            None => Err(LogoError::doesnt_like("double", inputs[0].clone())),
+        }
+    }
+}
+
+#[test]
+fn register_primitive() -> Result<(), Box<dyn std::error::Error>> {
+    let out = Output::default();
+    let mut logo = Interpreter::new(Config::default());
+    logo.set_stdout(out.clone());
+    logo.register(Double);
+
+    assert_eq!(logo.eval("double 4")?, Value::Num(8.));
+    logo.run("print DBL double 1 run [ print 3 ]")?;
+    assert_eq!(String::from_utf8(out.0.lock().unwrap().clone())?, "4\n3\n");
+    assert!(logo.eval("double \"x").is_err());
+    assert!(logo.names().any(|n| n == "dbl"));
+
+    Ok(())
+}
+
+/// Counts the lines and polygons and keeps the labels drawn on it.
+#[derive(Default)]
+struct Tally {
+    lines: usize,
+    polygons: usize,
+    labels: Vec<String>,
+}
+
+impl Canvas for Tally {
+    fn pen_down(&mut self, _: Point) {}
+
+    fn pen_up(&mut self) {}
+
+    fn move_to(&mut self, _: Point) {}
+
+    fn line_by(&mut self, _: f32, _: f32) {
+        self.lines += 1;
+    }
+
+    fn set_color(&mut self, _: &str) {}
+
+    fn text(&mut self, _: Point, _: f32, _: f32, text: &str) {
+        self.labels.push(text.to_string());
+    }
+
+    fn polygon(&mut self, _: &[Point]) {
+        self.polygons += 1;
+    }
+
+    fn clear(&mut self) {
+        *self = Tally::default();
+    }
+}
+
+#[test]
+fn keep_primitives() -> Result<(), Box<dyn std::error::Error>> {
+    let mut logo = Interpreter::new(Config::default());
+    logo.register(Double);
+
+    for (src, name) in [
+        ("to fd :a :b print :a end", "fd"),
+        ("to print end", "print"),
+        ("to Stamp :a end", "Stamp"),
+        ("to dbl end", "dbl"),
+    ] {
+        let e = logo.run(src).unwrap_err();
+        assert_eq!(e.to_string(), format!("{name} is a primitive at 1:1"));
+    }
+    // They still take as many inputs as they did.
+    assert_eq!(logo.eval("fd 10 dbl 2")?, Value::Num(4.));
+    assert_eq!(
+        logo.run("fd 1 2").unwrap_err().to_string(),
+        "You don't say what to do with 2 at 1:6"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn replay_events() -> Result<(), Box<dyn std::error::Error>> {
+    let mut logo = Interpreter::new(Config::default());
+
+    logo.run("fd 10 cs repeat 4 [ fd 10 rt 90 ] setcolor \"red pu fd 5 label \"hi")?;
+    let mut tally = Tally::default();
+    logo.replay(&mut tally);
+    assert_eq!(tally.lines, 4);
+    assert_eq!(tally.labels, ["hi"]);
+
+    let events = logo.events();
+    assert!(events.contains(&Event::Clear));
+    assert!(events.contains(&Event::SetColor("red".to_string())));
+    assert!(matches!(
+        events.last(),
+        Some(Event::Text { height: 100., .. })
+    ));
+    // Reading the events or drawing them leaves them as they were.
+    logo.run("pd fd 10")?;
+    let seen = logo.events().len();
+    logo.replay(&mut tally);
+    assert_eq!(logo.events().len(), seen);
+    assert!(matches!(logo.events().last(), Some(Event::LineBy(..))));
+
+    Ok(())
+}
+
+#[test]
+fn stamp_shapes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut logo = Interpreter::new(Config::default());
+
+    logo.run("setshape \"arrow repeat 3 [ stamp fd 50 ] setshape [0 10 5 0 -5 0]")?;
+    let mut tally = Tally::default();
+    logo.replay(&mut tally);
+    // Three stamps, then the turtle itself.
+    assert_eq!(tally.polygons, 4);
+
+    let arrows = logo.events().iter().filter(|e| match e {
+        Event::Polygon(corners) => corners.len() == 7,
+        _ => false,
+    });
+    assert_eq!(arrows.count(), 3);
+    assert_eq!(
+        logo.run("setshape [1 2 3]").unwrap_err().to_string(),
+        "setshape doesn't like [ 1 2 3 ] as input at 1:1"
+    );
+
+    Ok(())
+}
+
+/// Keeps where each line drawn on it ends, drawing only what a canvas
+/// must.
+#[derive(Default)]
+struct Ends {
+    pen: Option<Point>,
+    ends: Vec<Point>,
+}
+
+impl Canvas for Ends {
+    fn pen_down(&mut self, at: Point) {
+        self.pen = Some(at);
+    }
+
+    fn pen_up(&mut self) {
+        self.pen = None;
+    }
+
+    fn move_to(&mut self, _: Point) {}
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        if let Some(pen) = &mut self.pen {
+            (pen.x, pen.y) = (pen.x + dx, pen.y + dy);
+            self.ends.push(*pen);
+        }
+    }
+
+    fn set_color(&mut self, _: &str) {}
+
+    fn text(&mut self, _: Point, _: f32, _: f32, _: &str) {}
+
+    fn clear(&mut self) {}
+}
+
+#[test]
+fn default_polygons() -> Result<(), Box<dyn std::error::Error>> {
+    let mut plain = Interpreter::new(Config::default());
+    let mut logo = Interpreter::new(Config::default());
+
+    plain.run("ht fd 10")?;
+    logo.run("ht stamp fd 10")?;
+    let (mut plain_ends, mut ends) = (Ends::default(), Ends::default());
+    plain.replay(&mut plain_ends);
+    logo.replay(&mut ends);
+    // The turtle's triangle, closed, then the line from the turtle on.
+    assert_eq!(ends.ends.len(), 4);
+    assert_eq!(ends.ends.last(), plain_ends.ends.last());
+
+    Ok(())
+}
+
+#[test]
+fn pen_sizes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut logo = Interpreter::new(Config::default());
+
+    logo.run("setpensize 3 setlinecap \"square")?;
+    assert_eq!(logo.eval("pensize")?.to_string(), "[ 3 3 ]");
+    let style = LineStyle {
+        width: 3.,
+        cap: LineCap::Square,
+        join: LineJoin::Miter,
+    };
+    assert!(logo.events().contains(&Event::SetLineStyle(style)));
+    assert_eq!(
+        logo.run("setpensize [2 4]").unwrap_err().to_string(),
+        "setpensize doesn't like [ 2 4 ] as input at 1:1"
+    );
+    assert!(logo.run("setlinejoin \"sharp").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn reverse_twice() -> Result<(), Box<dyn std::error::Error>> {
+    let lines = "ht setpensize 7 setpencolor \"teal fd 50 rt 30 bk 80 ";
+    let mut painted = Interpreter::new(Config::default());
+    let mut logo = Interpreter::new(Config::default());
+
+    painted.run(lines)?;
+    logo.run(lines)?;
+    logo.run("penreverse repeat 5 [ fd 100 rt 144 ]")?;
+    assert_eq!(logo.eval("penmode")?.to_string(), "reverse");
+    let options = PngOptions::default();
+    assert!(logo.render_png(options)? != painted.render_png(options)?);
+    // Drawn in reverse again, the star goes away.
+    logo.run("penreverse repeat 5 [ fd 100 rt 144 ]")?;
+    assert!(logo.render_png(options)? == painted.render_png(options)?);
+
+    Ok(())
+}
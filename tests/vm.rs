@@ -0,0 +1,65 @@
+use assert_cmd::prelude::*;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn vm() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir();
+    for name in ["star", "fern", "logo", "tree"] {
+        let svg = dir.join(format!("logo-vm-{name}.svg"));
+        let mut cmd = Command::cargo_bin("logo")?;
+
+        cmd.arg("--vm").arg("-o").arg(&svg);
+        cmd.arg(format!("progs/{name}.logo"));
+        cmd.assert().success();
+
+        Command::new("diff")
+            .arg(&svg)
+            .arg(format!("./progs/{name}.svg"))
+            .assert()
+            .success();
+    }
+
+    Ok(())
+}
+
+/// Every program runs the same on both engines, drawing the same picture.
+#[test]
+fn engines_agree() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir();
+    let mut files: Vec<_> = fs::read_dir("progs")?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|file| file.extension().is_some_and(|ext| ext == "logo"));
+    files.sort();
+    for file in files {
+        // It draws at random.
+        if file.ends_with("square.logo") {
+            continue;
+        }
+        let stem = file.file_stem().unwrap().to_string_lossy();
+        let tree_svg = dir.join(format!("logo-tree-{stem}.svg"));
+        let vm_svg = dir.join(format!("logo-vm-{stem}.svg"));
+        let _ = fs::remove_file(&tree_svg);
+        let _ = fs::remove_file(&vm_svg);
+
+        let tree = Command::cargo_bin("logo")?
+            .arg("-o")
+            .arg(&tree_svg)
+            .arg(&file)
+            .output()?;
+        let vm = Command::cargo_bin("logo")?
+            .arg("--vm")
+            .arg("-o")
+            .arg(&vm_svg)
+            .arg(&file)
+            .output()?;
+        let file = file.display();
+        assert_eq!(tree.status.code(), vm.status.code(), "{file}");
+        assert_eq!(tree.stdout, vm.stdout, "{file}");
+        assert_eq!(tree.stderr, vm.stderr, "{file}");
+        assert_eq!(fs::read(&tree_svg).ok(), fs::read(&vm_svg).ok(), "{file}");
+    }
+
+    Ok(())
+}